@@ -1,52 +1,68 @@
+use crate::constants::{AGENT_SPEED, CROWD_AGENT_SPEED, TRAIL_LENGTH};
+use crate::maze::Maze;
 use crate::node::Node;
 use macroquad::prelude::*;
 use std::collections::VecDeque;
 
 #[derive(Clone, Debug)]
 pub struct Agent {
-    pub position: Vec2,
-    pub target: Option<Vec2>,
+    pub position: Vec3, // z is the floor, interpolated while on stairs
+    pub target: Option<Vec3>,
     pub grid_path: VecDeque<Node>,
     pub color: Color,
     pub speed: f32, // Tiles per second
     pub path_index: usize,
     pub is_main: bool,
-    pub trail: Vec<Vec2>,
-    pub start_position: Vec2,         // Store starting position for reset
-    pub initial_target: Option<Vec2>, // Store initial target for reset
+    pub trail: Vec<Vec3>,
+    pub trail_length: usize,
+    pub start_position: Vec3,         // Store starting position for reset
+    pub initial_target: Option<Node>, // Store initial target for reset
     pub heuristic_index: usize,       // Assigned heuristic index
     pub blocked_time: f32,            // Time spent blocked
+    pub waypoints: VecDeque<Node>,    // Remaining tour stops (main agent)
 }
 
 impl Agent {
     pub fn new(start_node: Node, color: Color, is_main: bool) -> Self {
         Self {
-            position: vec2(start_node.x as f32, start_node.y as f32),
+            position: start_node.position(),
             target: None,
             grid_path: VecDeque::new(),
             color,
-            speed: if is_main { 4.0 } else { 3.0 }, // Slower speed
+            speed: if is_main {
+                AGENT_SPEED
+            } else {
+                CROWD_AGENT_SPEED
+            },
             path_index: 0,
             is_main,
             trail: Vec::new(),
-            start_position: vec2(start_node.x as f32, start_node.y as f32),
+            trail_length: TRAIL_LENGTH,
+            start_position: start_node.position(),
             initial_target: None,
             heuristic_index: 0,
             blocked_time: 0.0,
+            waypoints: VecDeque::new(),
         }
     }
 
+    pub fn grid_position(&self) -> Node {
+        Node::at(
+            self.position.x.round() as usize,
+            self.position.y.round() as usize,
+            self.position.z.round() as usize,
+        )
+    }
+
     pub fn set_path(&mut self, path: Vec<Node>) {
         self.grid_path = VecDeque::from(path);
         self.path_index = 0;
 
         // Remove start node from path if it matches current position to avoid stutter
-        if let Some(first) = self.grid_path.front() {
-            if (first.x as f32 - self.position.x).abs() < 0.1
-                && (first.y as f32 - self.position.y).abs() < 0.1
-            {
-                self.grid_path.pop_front();
-            }
+        if let Some(first) = self.grid_path.front()
+            && first.position().distance(self.position) < 0.1
+        {
+            self.grid_path.pop_front();
         }
     }
 
@@ -55,27 +71,38 @@ impl Agent {
             let direction = target - self.position;
             let distance = direction.length();
 
-            if distance < self.speed * dt {
+            // Hops that aren't between touching tiles (around the seam of a polar ring,
+            // across a change in sector count) happen in one go
+            if distance < self.speed * dt || distance > 2.5 {
                 self.position = target;
                 self.target = None;
             } else {
                 self.position += direction.normalize() * self.speed * dt;
             }
         } else if let Some(next_node) = self.grid_path.pop_front() {
-            self.target = Some(vec2(next_node.x as f32, next_node.y as f32));
+            self.target = Some(next_node.position());
+        }
+
+        // Tick off tour stops as they are passed
+        while let Some(stop) = self.waypoints.front() {
+            if self.position.distance(stop.position()) < 0.5 {
+                self.waypoints.pop_front();
+            } else {
+                break;
+            }
         }
 
         // Record trail for all agents
         if self.trail.is_empty() {
             self.trail.push(self.position);
-        } else if let Some(last) = self.trail.last() {
-            if last.distance(self.position) > 0.5 {
-                self.trail.push(self.position);
-                if self.trail.len() > 150 {
-                    // Limit trail length for performance/visuals
-                    self.trail.remove(0);
-                }
-            }
+        } else if let Some(last) = self.trail.last()
+            && last.distance(self.position) > 0.5
+        {
+            self.trail.push(self.position);
+        }
+        // Limit trail length for performance/visuals
+        if self.trail.len() > self.trail_length {
+            self.trail.drain(..self.trail.len() - self.trail_length);
         }
     }
 
@@ -87,35 +114,45 @@ impl Agent {
         self.blocked_time = 0.0;
     }
 
-    pub fn draw(&self, cell_size: f32) {
-        let center_x = self.position.x * cell_size + cell_size / 2.0;
-        let center_y = self.position.y * cell_size + cell_size / 2.0;
+    pub fn draw(&self, maze: &Maze, cell_size: f32) {
+        let size = maze.view_cell_size(cell_size);
+        let project = |p: Vec3| maze.project(p, cell_size);
 
         // Draw Trail (for all agents)
         for i in 0..self.trail.len().saturating_sub(1) {
-            let p1 = self.trail[i];
-            let p2 = self.trail[i + 1];
+            let (t1, t2) = (self.trail[i], self.trail[i + 1]);
+            if !maze.visible(t1.z.round() as usize) || !maze.visible(t2.z.round() as usize) {
+                continue;
+            }
+            let p1 = project(t1);
+            let p2 = project(t2);
             // Fade trail based on index, but kept more visible
             let alpha = 0.8 * (i as f32 / self.trail.len() as f32);
             draw_line(
-                p1.x * cell_size + cell_size / 2.0,
-                p1.y * cell_size + cell_size / 2.0,
-                p2.x * cell_size + cell_size / 2.0,
-                p2.y * cell_size + cell_size / 2.0,
+                p1.x,
+                p1.y,
+                p2.x,
+                p2.y,
                 4.0, // Thicker line
                 Color::new(self.color.r, self.color.g, self.color.b, alpha),
             );
         }
 
+        if !maze.visible(self.position.z.round() as usize) {
+            return;
+        }
+        let center = project(self.position);
+        let (center_x, center_y) = (center.x, center.y);
+
         // Draw Outline
-        draw_circle(center_x, center_y, cell_size / 1.5, WHITE); // Larger outline
+        draw_circle(center_x, center_y, size / 1.5, WHITE); // Larger outline
 
         // Draw Main Body
-        draw_circle(center_x, center_y, cell_size / 1.8, self.color); // Larger body
+        draw_circle(center_x, center_y, size / 1.8, self.color); // Larger body
 
         if self.is_main {
             // Draw Star/Icon for Main
-            draw_circle(center_x, center_y, cell_size / 3.0, YELLOW);
+            draw_circle(center_x, center_y, size / 3.0, YELLOW);
         }
     }
 }
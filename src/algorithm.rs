@@ -4,16 +4,15 @@
 
 use macroquad::prelude::*;
 use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashMap};
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::path;
 
-use crate::constants::{COLOR_PATH, TILE_SIZE};
+use crate::constants::{COLOR_CURRENT, COLOR_OPEN, COLOR_PATH, OVERLAY_TEXT_MIN_CELL, TILE_SIZE};
+use crate::helper::gradient;
 use crate::heuristic::*;
 use crate::maze::{Maze, Tile};
 use crate::node::Node;
 
-type HeuristicFn = fn(Node, Node) -> f32;
-
 #[derive(PartialEq, Clone, Copy)]
 pub struct State {
     pub cost: f32,
@@ -37,85 +36,220 @@ impl PartialOrd for State {
     }
 }
 
+// A neighbor whose g improved during an expansion, and was pushed with this f
+#[derive(Clone, Copy, Debug)]
+pub struct Push {
+    pub node: Node,
+    pub g: f32,
+    pub f: f32,
+}
+
+// What to show on top of the explored cells
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScoreOverlay {
+    Off,
+    Values, // g, h and f printed in each cell, once cells are big enough to read
+    G,      // Cells colored by g, h or f from low (blue) to high (red)
+    H,
+    F,
+}
+
+impl ScoreOverlay {
+    pub fn next(self) -> Self {
+        match self {
+            ScoreOverlay::Off => ScoreOverlay::Values,
+            ScoreOverlay::Values => ScoreOverlay::G,
+            ScoreOverlay::G => ScoreOverlay::H,
+            ScoreOverlay::H => ScoreOverlay::F,
+            ScoreOverlay::F => ScoreOverlay::Off,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ScoreOverlay::Off => "Off",
+            ScoreOverlay::Values => "g/h/f",
+            ScoreOverlay::G => "g",
+            ScoreOverlay::H => "h",
+            ScoreOverlay::F => "f",
+        }
+    }
+}
+
+// One pop of the open heap and everything it pushed
+#[derive(Clone, Debug)]
+pub struct Expansion {
+    pub node: Node,
+    pub g: f32,
+    pub f: f32,
+    pub pushed: Vec<Push>,
+}
+
 pub struct AStarVisualizer {
     pub open: BinaryHeap<State>,
+    pub closed: HashSet<Node>,
     pub came_from: HashMap<Node, Node>,
     pub g_score: HashMap<Node, f32>,
+    pub f_score: HashMap<Node, f32>, // f of each node's latest push
     pub path: Option<Vec<Node>>,
     pub found: bool,
     pub start: Node,
     pub goal: Node,
+    pub goals: Vec<Node>,
+    pub history: Vec<Expansion>, // Every expansion so far, including undone ones
+    pub cursor: usize,           // How many of them the current state reflects
+    pub weight: f32,             // f = g + weight * h; above 1 trades optimality for speed
+    pub optimal: Option<f32>,    // Cost to compare the result against, when known
 }
 
 impl AStarVisualizer {
     pub fn new(maze: &Maze) -> Self {
-        let start = maze.start;
-        let goal = maze.goal;
+        Self::with_goals(maze, vec![maze.goal])
+    }
 
-        let mut open = BinaryHeap::new();
-        let mut g_score = HashMap::new();
+    // Stops at whichever goal is popped first
+    pub fn with_goals(maze: &Maze, goals: Vec<Node>) -> Self {
+        let goals = if goals.is_empty() {
+            vec![maze.goal]
+        } else {
+            goals
+        };
+        let mut solver = Self {
+            open: BinaryHeap::new(),
+            closed: HashSet::new(),
+            came_from: HashMap::new(),
+            g_score: HashMap::new(),
+            f_score: HashMap::new(),
+            path: None,
+            found: false,
+            start: maze.start,
+            goal: goals[0],
+            goals,
+            history: Vec::new(),
+            cursor: 0,
+            weight: 1.0,
+            optimal: None,
+        };
+        solver.rewind();
+        solver
+    }
 
-        g_score.insert(start, 0.0);
-        open.push(State {
+    // Back to the state before the first expansion; the history is kept
+    fn rewind(&mut self) {
+        self.open.clear();
+        self.open.push(State {
             cost: 0.0,
-            pos: start,
+            pos: self.start,
         });
+        self.closed.clear();
+        self.came_from.clear();
+        self.g_score.clear();
+        self.g_score.insert(self.start, 0.0);
+        self.f_score.clear();
+        self.path = None;
+        self.found = false;
+        self.goal = self.goals[0];
+        self.cursor = 0;
+    }
 
-        Self {
-            open,
-            came_from: HashMap::new(),
-            g_score,
-            path: None,
-            found: false,
-            start,
-            goal,
+    // Rebuild the open and closed sets as they were after `step` expansions by replaying
+    // the log. Replaying pushes the same entries in the same order, so the heap pops
+    // exactly as it did the first time
+    pub fn seek(&mut self, step: usize) {
+        let step = step.min(self.history.len());
+        if step < self.cursor {
+            self.rewind();
+        }
+        while self.cursor < step {
+            let expansion = self.history[self.cursor].clone();
+            self.apply(&expansion);
         }
     }
 
-    pub fn step(&mut self, maze: &Maze, heuristic: HeuristicFn) {
-        if self.found || self.open.is_empty() {
-            return;
-        }
+    pub fn step_back(&mut self) {
+        self.seek(self.cursor.saturating_sub(1));
+    }
 
-        if let Some(State { cost: _, pos }) = self.open.pop() {
-            if pos == self.goal {
-                self.found = true;
-                self.path = Some(Self::reconstruct_path(&self.came_from, pos));
-                return;
-            }
+    // The expansion that produced the current state, if any
+    pub fn last_expansion(&self) -> Option<&Expansion> {
+        self.cursor.checked_sub(1).map(|i| &self.history[i])
+    }
 
-            let current_g = *self.g_score.get(&pos).unwrap_or(&f32::INFINITY);
-            let dirs = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+    fn apply(&mut self, expansion: &Expansion) {
+        self.open.pop();
+        self.cursor += 1;
+        let pos = expansion.node;
+        self.closed.insert(pos);
 
-            for (dx, dy) in dirs {
-                let nx = pos.x as isize + dx;
-                let ny = pos.y as isize + dy;
+        if self.goals.contains(&pos) {
+            self.goal = pos;
+            self.found = true;
+            self.path = Some(Self::reconstruct_path(&self.came_from, pos));
+            return;
+        }
 
-                if !Maze::in_bounds(nx, ny, maze.width, maze.height) {
-                    continue;
-                }
+        for push in &expansion.pushed {
+            self.came_from.insert(push.node, pos);
+            self.g_score.insert(push.node, push.g);
+            self.f_score.insert(push.node, push.f);
+            self.open.push(State {
+                cost: push.f,
+                pos: push.node,
+            });
+        }
+    }
 
-                let neighbor = Node::new(nx as usize, ny as usize);
+    // Multi-target heuristic: distance to the closest goal
+    fn nearest_goal_h(heuristic: &dyn Heuristic, node: Node, goals: &[Node]) -> f32 {
+        goals
+            .iter()
+            .map(|&g| heuristic.estimate(node, g))
+            .fold(f32::INFINITY, f32::min)
+    }
 
-                if maze.grid[neighbor.y][neighbor.x] == Tile::Wall {
+    // Redo a logged expansion after stepping back, otherwise expand the next node
+    pub fn step(&mut self, maze: &Maze, heuristic: &dyn Heuristic) {
+        if self.found {
+            return;
+        }
+        if self.cursor < self.history.len() {
+            self.seek(self.cursor + 1);
+            return;
+        }
+
+        let Some(&State { cost, pos }) = self.open.peek() else {
+            return;
+        };
+        let current_g = *self.g_score.get(&pos).unwrap_or(&f32::INFINITY);
+        let mut expansion = Expansion {
+            node: pos,
+            g: current_g,
+            f: cost,
+            pushed: Vec::new(),
+        };
+
+        if !self.goals.contains(&pos) {
+            for neighbor in maze.moves(pos) {
+                if maze[neighbor] == Tile::Wall {
                     continue;
                 }
 
-                let tentative_g = current_g + 1.0;
+                let tentative_g = current_g + maze.move_cost(pos, neighbor);
                 let neighbor_g = *self.g_score.get(&neighbor).unwrap_or(&f32::INFINITY);
 
                 if tentative_g < neighbor_g {
-                    self.came_from.insert(neighbor, pos);
-                    self.g_score.insert(neighbor, tentative_g);
-
-                    let f_score = tentative_g + heuristic(neighbor, self.goal);
-                    self.open.push(State {
-                        cost: f_score,
-                        pos: neighbor,
+                    expansion.pushed.push(Push {
+                        node: neighbor,
+                        g: tentative_g,
+                        f: tentative_g
+                            + self.weight * Self::nearest_goal_h(heuristic, neighbor, &self.goals),
                     });
                 }
             }
         }
+
+        self.history.push(expansion.clone());
+        self.apply(&expansion);
     }
 
     fn reconstruct_path(came_from: &HashMap<Node, Node>, mut current: Node) -> Vec<Node> {
@@ -128,31 +262,159 @@ impl AStarVisualizer {
         total_path
     }
 
-    pub fn draw(&self, maze: &Maze, cell_size: f32) {
-        for node in self.came_from.keys() {
-            if *node != maze.start && *node != maze.goal {
-                draw_rectangle(
-                    node.x as f32 * cell_size,
-                    node.y as f32 * cell_size,
-                    cell_size,
-                    cell_size,
-                    COLOR_PATH,
-                );
+    // Nodes waiting in the open heap; entries for nodes expanded since are stale
+    pub fn frontier(&self) -> HashSet<Node> {
+        self.open
+            .iter()
+            .map(|s| s.pos)
+            .filter(|n| !self.closed.contains(n))
+            .collect()
+    }
+
+    // g, h and f of a node that has been reached; h is before weighting
+    pub fn scores(&self, node: Node) -> Option<(f32, f32, f32)> {
+        let g = *self.g_score.get(&node)?;
+        let f = *self.f_score.get(&node)?;
+        Some((g, (f - g) / self.weight, f))
+    }
+
+    // Cost of the path found, once there is one
+    pub fn cost(&self) -> Option<f32> {
+        self.found
+            .then(|| self.g_score.get(&self.goal).copied())
+            .flatten()
+    }
+
+    // Cheapest cost from the start to any goal, from a plain Dijkstra run
+    pub fn optimal_cost(maze: &Maze, goals: &[Node]) -> Option<f32> {
+        Self::find_path_to_any(maze, maze.start, goals, &ZERO, &[])
+            .map(|path| maze.path_cost(&path))
+    }
+
+    // True cost from the nearest source to every reachable cell (plain Dijkstra; every
+    // move costs the same both ways, so these are also the costs back to the sources)
+    pub fn distances(maze: &Maze, sources: &[Node]) -> HashMap<Node, f32> {
+        let mut distance: HashMap<Node, f32> = sources.iter().map(|&n| (n, 0.0)).collect();
+        let mut open: BinaryHeap<State> = sources
+            .iter()
+            .map(|&pos| State { cost: 0.0, pos })
+            .collect();
+        while let Some(State { cost, pos }) = open.pop() {
+            if cost > distance[&pos] {
+                continue;
+            }
+            for next in maze.moves(pos) {
+                if maze[next] == Tile::Wall {
+                    continue;
+                }
+                let d = cost + maze.move_cost(pos, next);
+                if distance.get(&next).is_none_or(|&old| d < old) {
+                    distance.insert(next, d);
+                    open.push(State { cost: d, pos: next });
+                }
             }
         }
+        distance
+    }
+
+    // `zoom` is the current screen pixels per maze pixel
+    pub fn draw(&self, maze: &Maze, cell_size: f32, overlay: ScoreOverlay, zoom: f32) {
+        let is_endpoint = |n: &Node| *n == maze.start || self.goals.contains(n);
+        let frontier = self.frontier();
+        let current = self.last_expansion().map(|e| e.node);
+
+        let pick = |(g, h, f): (f32, f32, f32)| match overlay {
+            ScoreOverlay::G => Some(g),
+            ScoreOverlay::H => Some(h),
+            ScoreOverlay::F => Some(f),
+            ScoreOverlay::Off | ScoreOverlay::Values => None,
+        };
+        let range = self
+            .came_from
+            .keys()
+            .filter_map(|&n| self.scores(n).and_then(pick))
+            .fold(None, |r: Option<(f32, f32)>, v| {
+                Some(r.map_or((v, v), |(lo, hi)| (lo.min(v), hi.max(v))))
+            });
+
+        for node in self.came_from.keys().filter(|n| !is_endpoint(n)) {
+            let color = match (range, self.scores(*node).and_then(pick)) {
+                (Some((lo, hi)), Some(v)) => {
+                    let mut c = gradient((v - lo) / (hi - lo).max(f32::EPSILON));
+                    // Keep the frontier recognizable on top of the gradient
+                    if !frontier.contains(node) {
+                        c.a = 0.75;
+                    }
+                    c
+                }
+                _ if frontier.contains(node) => COLOR_OPEN,
+                _ => COLOR_PATH,
+            };
+            maze.draw_tile(*node, cell_size, color);
+        }
+
+        if let Some(node) = current.filter(|n| !is_endpoint(n)) {
+            maze.draw_tile(node, cell_size, COLOR_CURRENT);
+        }
 
         if let Some(path) = &self.path {
             for node in path {
                 if *node != maze.start && *node != maze.goal {
-                    draw_rectangle(
-                        node.x as f32 * cell_size,
-                        node.y as f32 * cell_size,
-                        cell_size,
-                        cell_size,
-                        GREEN,
-                    );
+                    maze.draw_tile(*node, cell_size, GREEN);
                 }
             }
+
+            // Link the stairs across floor panels when several floors are shown
+            for pair in path.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                if a.z != b.z && maze.visible(a.z) && maze.visible(b.z) {
+                    let (pa, pb) = (maze.center(a, cell_size), maze.center(b, cell_size));
+                    draw_line(pa.x, pa.y, pb.x, pb.y, 2.0, GREEN);
+                }
+            }
+        }
+
+        if overlay != ScoreOverlay::Off {
+            self.draw_values(maze, cell_size, overlay, zoom);
+        }
+    }
+
+    // Numbers only fit once cells are zoomed in far enough
+    fn draw_values(&self, maze: &Maze, cell_size: f32, overlay: ScoreOverlay, zoom: f32) {
+        let cell = maze.view_cell_size(cell_size);
+        if cell * zoom < OVERLAY_TEXT_MIN_CELL {
+            return;
+        }
+
+        let font_size = cell / 3.0;
+        for (&node, _) in self.came_from.iter().filter(|(n, _)| maze.visible(n.z)) {
+            let Some((g, h, f)) = self.scores(node) else {
+                continue;
+            };
+            let lines = match overlay {
+                ScoreOverlay::Values => vec![
+                    format!("g{:.0}", g),
+                    format!("h{:.1}", h),
+                    format!("f{:.1}", f),
+                ],
+                ScoreOverlay::G => vec![format!("{:.0}", g)],
+                ScoreOverlay::H => vec![format!("{:.1}", h)],
+                ScoreOverlay::F => vec![format!("{:.1}", f)],
+                ScoreOverlay::Off => Vec::new(),
+            };
+
+            let c = maze.center(node, cell_size);
+            let top = c.y - font_size * (lines.len() as f32 - 1.0) / 2.0;
+            for (i, line) in lines.iter().enumerate() {
+                let size = measure_text(line, None, font_size as u16, 1.0);
+                draw_text(
+                    line,
+                    c.x - size.width / 2.0,
+                    top + i as f32 * font_size + size.offset_y / 2.0,
+                    font_size,
+                    BLACK,
+                );
+            }
         }
     }
 
@@ -160,7 +422,17 @@ impl AStarVisualizer {
         maze: &Maze,
         start: Node,
         goal: Node,
-        heuristic: HeuristicFn,
+        heuristic: &dyn Heuristic,
+        obstacles: &[crate::obstacle::DynamicObstacle],
+    ) -> Option<Vec<Node>> {
+        Self::find_path_to_any(maze, start, &[goal], heuristic, obstacles)
+    }
+
+    pub fn find_path_to_any(
+        maze: &Maze,
+        start: Node,
+        goals: &[Node],
+        heuristic: &dyn Heuristic,
         obstacles: &[crate::obstacle::DynamicObstacle],
     ) -> Option<Vec<Node>> {
         let mut open = BinaryHeap::new();
@@ -174,40 +446,32 @@ impl AStarVisualizer {
         });
 
         while let Some(State { cost: _, pos }) = open.pop() {
-            if pos == goal {
+            if goals.contains(&pos) {
                 return Some(Self::reconstruct_path(&came_from, pos));
             }
 
             let current_g = *g_score.get(&pos).unwrap_or(&f32::INFINITY);
-            let dirs = [(0, 1), (1, 0), (0, -1), (-1, 0)];
-
-            for (dx, dy) in dirs {
-                let nx = pos.x as isize + dx;
-                let ny = pos.y as isize + dy;
-
-                if !Maze::in_bounds(nx, ny, maze.width, maze.height) {
-                    continue;
-                }
-
-                let neighbor = Node::new(nx as usize, ny as usize);
 
-                if maze.grid[neighbor.y][neighbor.x] == Tile::Wall {
+            for neighbor in maze.moves(pos) {
+                if maze[neighbor] == Tile::Wall {
                     continue;
                 }
 
                 // Obstacle check
-                if obstacles.iter().any(|obs| obs.position == neighbor) && neighbor != goal {
+                if obstacles.iter().any(|obs| obs.position == neighbor)
+                    && !goals.contains(&neighbor)
+                {
                     continue;
                 }
 
-                let tentative_g = current_g + 1.0;
+                let tentative_g = current_g + maze.move_cost(pos, neighbor);
                 let neighbor_g = *g_score.get(&neighbor).unwrap_or(&f32::INFINITY);
 
                 if tentative_g < neighbor_g {
                     came_from.insert(neighbor, pos);
                     g_score.insert(neighbor, tentative_g);
 
-                    let f_score = tentative_g + heuristic(neighbor, goal);
+                    let f_score = tentative_g + Self::nearest_goal_h(heuristic, neighbor, goals);
                     open.push(State {
                         cost: f_score,
                         pos: neighbor,
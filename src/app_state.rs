@@ -1,9 +1,18 @@
-use crate::algorithm::AStarVisualizer;
+use crate::agent::Agent;
+use crate::algorithm::{AStarVisualizer, ScoreOverlay};
+use crate::ara::Ara;
+use crate::check::HeuristicCheck;
 use crate::config::AppConfig;
+use crate::constants::MAX_HEURISTIC_WEIGHT;
+use crate::editor::Editor;
 use crate::generator::Algorithm;
 use crate::generator::MazeVisualizer;
+use crate::heuristic::Heuristics;
 use crate::maze::Maze;
 use crate::node::Node;
+use crate::race::Race;
+use crate::tour::{Tour, plan_tour};
+use crate::view::View;
 
 pub enum AppMode {
     MazeGeneration,
@@ -11,43 +20,326 @@ pub enum AppMode {
     Idle,
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GoalMode {
+    Single,  // Primary goal only
+    Nearest, // Stop at whichever goal is reached first
+    Tour,    // Visit every goal in the shortest order
+}
+
+impl GoalMode {
+    pub fn next(self) -> Self {
+        match self {
+            GoalMode::Single => GoalMode::Nearest,
+            GoalMode::Nearest => GoalMode::Tour,
+            GoalMode::Tour => GoalMode::Single,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GoalMode::Single => "Single",
+            GoalMode::Nearest => "Nearest",
+            GoalMode::Tour => "Tour",
+        }
+    }
+
+    // Goals the main agent is currently heading for
+    pub fn targets(self, maze: &Maze, agent: &Agent) -> Vec<Node> {
+        match self {
+            GoalMode::Single => vec![maze.goal],
+            GoalMode::Nearest => maze.goals(),
+            GoalMode::Tour => agent.waypoints.front().copied().into_iter().collect(),
+        }
+    }
+}
+
 pub struct AppState {
     pub maze: Maze,
     pub generator: Option<MazeVisualizer>,
     pub solver: Option<AStarVisualizer>,
-    pub agents: Vec<crate::agent::Agent>,
+    pub race: Option<Race>,
+    pub heuristics: Heuristics,
+    pub heuristic: usize, // What the solver, ARA* and new main agents search with
+    pub race_heuristics: Vec<usize>, // Heuristics that get a lane
+    pub ara: Option<Ara>,
+    pub check: Option<HeuristicCheck>,
+    pub agents: Vec<Agent>,
     pub obstacles: Vec<crate::obstacle::DynamicObstacle>,
     pub global_target: Option<Node>,
     pub show_solver: bool,
+    pub paused: bool,
+    pub overlay: ScoreOverlay,
     pub mode: AppMode,
     pub config: AppConfig,
+    pub editor: Editor,
+    pub goal_mode: GoalMode,
+    pub tour: Option<Tour>,
+    pub view: View,
 }
 
 impl AppState {
     pub fn new(config: AppConfig) -> Self {
-        let maze = Maze::new(config.grid_width, config.grid_height);
+        let mut maze = Maze::with_topology(config.grid_width, config.grid_height, config.topology);
+        maze.connectivity = config.connectivity;
+        let heuristics = Heuristics::builtin();
 
         Self {
             maze,
             generator: None,
             solver: None,
+            race: None,
+            race_heuristics: (0..heuristics.len()).collect(),
+            heuristics,
+            heuristic: config.heuristic,
+            ara: None,
+            check: None,
             agents: Vec::new(),
             obstacles: Vec::new(),
             global_target: None,
             show_solver: true,
+            paused: false,
+            overlay: ScoreOverlay::Off,
             mode: AppMode::Idle,
             config,
+            editor: Editor::new(),
+            goal_mode: GoalMode::Single,
+            tour: None,
+            view: View::new(),
         }
     }
 
     pub fn reset_maze(&mut self) {
-        self.maze = Maze::new(self.config.grid_width, self.config.grid_height);
+        let shape = (self.maze.width, self.maze.height, self.maze.topology);
+        let (start, goals) = (self.maze.start, self.maze.goals());
+        let (floors, view, mask) = (self.maze.floors, self.maze.view, self.maze.mask.take());
+        self.maze = Maze::with_floors(
+            self.config.grid_width,
+            self.config.grid_height,
+            self.config.floors,
+            self.config.topology,
+        );
+        self.maze.mask = mask;
+        self.maze.connectivity = self.config.connectivity;
+
+        // Keep user-placed endpoints across regenerations when they still fit
+        let fits = goals.iter().chain([&start]).all(|n| self.maze.contains(*n));
+        if fits && floors == self.maze.floors && !goals.contains(&start) {
+            self.maze.start = start;
+            self.maze.goal = goals[0];
+            self.maze.extra_goals = goals[1..].to_vec();
+        }
+        if floors == self.maze.floors {
+            self.maze.view = view;
+        }
+        if shape != (self.maze.width, self.maze.height, self.maze.topology) {
+            self.fit_view();
+        }
+        self.clear_simulation();
+    }
+
+    // Swap in a finished maze (e.g. loaded from a file) and skip generation
+    pub fn load_maze(&mut self, maze: Maze) {
+        self.config.grid_width = maze.width;
+        self.config.grid_height = maze.height;
+        self.config.topology = maze.topology;
+        self.config.floors = maze.floors;
+        self.maze = maze;
+        self.maze.connectivity = self.config.connectivity;
+        self.maze_changed();
+        self.fit_view();
+        self.clear_simulation();
+        self.mode = AppMode::Pathfinding;
+    }
+
+    // Build a fresh maze step by step. A generator the maze can't take falls back
+    // inside `MazeVisualizer::new`; the config keeps the user's choice
+    pub fn start_generation(&mut self) {
+        self.generator = Some(MazeVisualizer::new(
+            &mut self.maze,
+            self.config.algorithm,
+            self.config.weave,
+            self.config.density,
+        ));
+        self.mode = AppMode::MazeGeneration;
+    }
+
+    // Size the cells so the whole maze fits the window, and undo any pan and zoom
+    pub fn fit_view(&mut self) {
+        self.config.fit_cell_size();
+        self.view.fit(
+            self.maze.pixel_size(self.config.cell_size),
+            self.config.maze_area(),
+        );
+    }
+
+    fn clear_simulation(&mut self) {
         self.generator = None;
         self.solver = None;
+        self.race = None;
+        self.ara = None;
+        self.check = None;
         self.agents.clear();
         self.obstacles.clear();
         self.global_target = None;
         self.show_solver = true;
         self.mode = AppMode::Idle;
+        self.editor.clear_history();
+        self.tour = None;
+    }
+
+    pub fn new_solver(&self) -> AStarVisualizer {
+        let goals = self.search_goals();
+        let mut solver = AStarVisualizer::with_goals(&self.maze, goals.clone());
+        solver.weight = self.config.heuristic_weight;
+        solver.optimal = AStarVisualizer::optimal_cost(&self.maze, &goals);
+        solver
+    }
+
+    // Anytime search from the slider weight, or from the top of its range when that is 1
+    pub fn new_ara(&self) -> Ara {
+        let weight = match self.config.heuristic_weight {
+            w if w > 1.0 => w,
+            _ => MAX_HEURISTIC_WEIGHT,
+        };
+        let goals = self.search_goals();
+        let mut ara = Ara::new(&self.maze, goals.clone(), weight);
+        ara.optimal = AStarVisualizer::optimal_cost(&self.maze, &goals);
+        ara
+    }
+
+    // Run after every generation, edit or change to the connectivity or landmarks. Only the
+    // selected heuristic looks at the maze again now; the others wait until they are used
+    pub fn maze_changed(&mut self) {
+        self.heuristics.invalidate();
+        self.prepare_heuristic(self.heuristic);
+    }
+
+    pub fn prepare_heuristic(&mut self, index: usize) {
+        self.heuristics.prepare(index, &self.maze, &self.config);
+    }
+
+    // True costs against one heuristic, for the admissibility overlay
+    pub fn new_check(&mut self, heuristic_index: usize) -> HeuristicCheck {
+        self.prepare_heuristic(heuristic_index);
+        HeuristicCheck::run(
+            &self.maze,
+            &self.search_goals(),
+            heuristic_index,
+            self.heuristics.get(heuristic_index),
+        )
+    }
+
+    // Goals the solver and the race search for under the current goal mode
+    fn search_goals(&self) -> Vec<Node> {
+        match self.goal_mode {
+            GoalMode::Nearest => self.maze.goals(),
+            GoalMode::Single | GoalMode::Tour => vec![self.maze.goal],
+        }
+    }
+
+    pub fn new_race(&mut self) -> Race {
+        for i in self.race_heuristics.clone() {
+            self.prepare_heuristic(i);
+        }
+        Race::new(
+            &self.maze,
+            &self.search_goals(),
+            &self.heuristics,
+            &self.race_heuristics,
+            self.config.heuristic_weight,
+        )
+    }
+
+    // Agent and obstacle settings from the panel, onto everything already on the maze
+    pub fn apply_tuning(&mut self) {
+        for agent in &mut self.agents {
+            agent.speed = self.config.speed_of(agent.is_main);
+            agent.trail_length = self.config.trail_length;
+        }
+        for obstacle in &mut self.obstacles {
+            obstacle.move_interval = self.config.obstacle_interval;
+        }
+    }
+
+    // Route a main agent from where it stands according to the goal mode
+    pub fn route_main_agent(&mut self, index: usize) {
+        self.prepare_heuristic(self.agents[index].heuristic_index);
+        let agent = &mut self.agents[index];
+        let (start, goal) = (self.maze.start, self.maze.goal);
+        agent.start_position = start.position();
+        agent.initial_target = Some(goal);
+        agent.target = None;
+        agent.grid_path.clear();
+        agent.waypoints.clear();
+
+        if self.goal_mode == GoalMode::Tour {
+            self.tour = plan_tour(&self.maze, agent.grid_position(), &self.maze.goals());
+            if let Some(tour) = &self.tour {
+                agent.waypoints = tour.order.iter().copied().collect();
+                agent.set_path(tour.path.clone());
+            }
+            return;
+        }
+
+        let goals = self.goal_mode.targets(&self.maze, agent);
+        let h_func = self.heuristics.get(agent.heuristic_index);
+        if let Some(path) = AStarVisualizer::find_path_to_any(
+            &self.maze,
+            agent.grid_position(),
+            &goals,
+            h_func,
+            &self.obstacles,
+        ) {
+            agent.set_path(path);
+        }
+    }
+
+    // Restart the solver and route every agent again after the maze was edited
+    pub fn replan(&mut self) {
+        self.prepare_heuristic(self.heuristic);
+        if self.solver.is_some() {
+            self.solver = Some(self.new_solver());
+        }
+        if self.race.is_some() {
+            self.race = Some(self.new_race());
+        }
+        if self.ara.is_some() {
+            self.ara = Some(self.new_ara());
+        }
+        if let Some(i) = self.check.as_ref().map(|c| c.heuristic_index) {
+            self.check = Some(self.new_check(i));
+        }
+
+        for i in 0..self.agents.len() {
+            if self.agents[i].is_main {
+                self.route_main_agent(i);
+                continue;
+            }
+
+            let agent = &mut self.agents[i];
+            let target_node = self.global_target.or(agent.initial_target);
+
+            agent.target = None;
+            agent.grid_path.clear();
+
+            let Some(target_node) = target_node else {
+                continue;
+            };
+            let index = agent.heuristic_index;
+            self.prepare_heuristic(index);
+            let agent = &mut self.agents[i];
+            let h_func = self.heuristics.get(index);
+
+            if let Some(path) = AStarVisualizer::find_path(
+                &self.maze,
+                agent.grid_position(),
+                target_node,
+                h_func,
+                &self.obstacles,
+            ) {
+                agent.set_path(path);
+            }
+        }
     }
 }
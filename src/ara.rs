@@ -0,0 +1,230 @@
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use macroquad::prelude::*;
+
+use crate::algorithm::State;
+use crate::constants::{ARA_WEIGHT_STEP, COLOR_OPEN, COLOR_PATH};
+use crate::heuristic::Heuristic;
+use crate::maze::{Maze, Tile};
+use crate::node::Node;
+
+// One answer of the anytime search
+pub struct Solution {
+    pub weight: f32,
+    pub path: Vec<Node>,
+    pub cost: f32,
+    pub bound: Option<f32>, // Proven cost / optimal ratio, at most `weight`; admissible h only
+    pub expansions: usize,
+}
+
+// Anytime Repairing A* (Likhachev et al.): weighted A* that starts greedy, then lowers
+// the weight and repairs its previous search instead of starting over. Nodes whose g
+// improves after they were expanded wait in `incons` until the next round
+pub struct Ara {
+    pub open: BinaryHeap<State>, // Lazy: entries not matching `f_score` are stale
+    pub open_set: HashSet<Node>,
+    pub f_score: HashMap<Node, f32>, // f of each open node's latest push
+    pub closed: HashSet<Node>,
+    pub incons: HashSet<Node>,
+    pub g_score: HashMap<Node, f32>,
+    pub came_from: HashMap<Node, Node>,
+    pub goals: Vec<Node>,
+    pub weight: f32,
+    pub solutions: Vec<Solution>,
+    pub expansions: usize,
+    pub done: bool,
+    pub optimal: Option<f32>, // Cost to compare against when no bound is proven
+}
+
+impl Ara {
+    pub fn new(maze: &Maze, goals: Vec<Node>, weight: f32) -> Self {
+        let start = State {
+            cost: 0.0,
+            pos: maze.start,
+        };
+        Self {
+            open: BinaryHeap::from([start]),
+            open_set: HashSet::from([maze.start]),
+            f_score: HashMap::from([(maze.start, 0.0)]),
+            closed: HashSet::new(),
+            incons: HashSet::new(),
+            g_score: HashMap::from([(maze.start, 0.0)]),
+            came_from: HashMap::new(),
+            goals,
+            weight,
+            solutions: Vec::new(),
+            expansions: 0,
+            done: false,
+            optimal: None,
+        }
+    }
+
+    fn g(&self, node: Node) -> f32 {
+        self.g_score.get(&node).copied().unwrap_or(f32::INFINITY)
+    }
+
+    fn h(&self, heuristic: &dyn Heuristic, node: Node) -> f32 {
+        self.goals
+            .iter()
+            .map(|&goal| heuristic.estimate(node, goal))
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    fn f(&self, heuristic: &dyn Heuristic, node: Node) -> f32 {
+        self.g(node) + self.weight * self.h(heuristic, node)
+    }
+
+    fn push(&mut self, heuristic: &dyn Heuristic, pos: Node) {
+        let cost = self.f(heuristic, pos);
+        self.open_set.insert(pos);
+        self.f_score.insert(pos, cost);
+        self.open.push(State { cost, pos });
+    }
+
+    // Cheapest goal reached so far
+    fn best_goal(&self) -> Option<Node> {
+        self.goals
+            .iter()
+            .copied()
+            .filter(|g| self.g(*g).is_finite())
+            .min_by(|a, b| self.g(*a).total_cmp(&self.g(*b)))
+    }
+
+    fn path_to(&self, goal: Node) -> Vec<Node> {
+        let mut path = vec![goal];
+        while let Some(&prev) = self.came_from.get(path.last().unwrap()) {
+            path.push(prev);
+        }
+        path.reverse();
+        path
+    }
+
+    // The latest, best path
+    pub fn path(&self) -> Option<&[Node]> {
+        self.solutions.last().map(|s| s.path.as_slice())
+    }
+
+    // One expansion, or closing the current round once no open node can beat the goal
+    pub fn step(&mut self, maze: &Maze, heuristic: &dyn Heuristic) {
+        if self.done {
+            return;
+        }
+
+        // Drop entries for nodes expanded or re-pushed since
+        while let Some(top) = self.open.peek()
+            && (!self.open_set.contains(&top.pos) || Some(&top.cost) != self.f_score.get(&top.pos))
+        {
+            self.open.pop();
+        }
+
+        let goal_g = self.best_goal().map_or(f32::INFINITY, |g| self.g(g));
+        let Some(&State { pos, .. }) = self.open.peek().filter(|s| s.cost < goal_g) else {
+            self.finish_round(maze, heuristic);
+            return;
+        };
+
+        self.open.pop();
+        self.open_set.remove(&pos);
+        self.closed.insert(pos);
+        self.expansions += 1;
+
+        let g = self.g(pos);
+        for next in maze.moves(pos) {
+            if maze[next] == Tile::Wall {
+                continue;
+            }
+            let tentative = g + maze.move_cost(pos, next);
+            if tentative >= self.g(next) {
+                continue;
+            }
+            self.g_score.insert(next, tentative);
+            self.came_from.insert(next, pos);
+            if self.closed.contains(&next) {
+                self.incons.insert(next);
+            } else {
+                self.push(heuristic, next);
+            }
+        }
+    }
+
+    // Publish this round's path, then lower the weight and reopen the inconsistent nodes
+    fn finish_round(&mut self, maze: &Maze, heuristic: &dyn Heuristic) {
+        if let Some(goal) = self.best_goal() {
+            let cost = self.g(goal);
+            // Every open or inconsistent node bounds the optimal cost from below, as long
+            // as h never overestimates; otherwise nothing is proven
+            let bound = heuristic.admissible(maze).then(|| {
+                let lower = self
+                    .open_set
+                    .iter()
+                    .chain(&self.incons)
+                    .map(|&n| self.g(n) + self.h(heuristic, n))
+                    .fold(f32::INFINITY, f32::min)
+                    .min(cost);
+                self.weight.min(cost / lower.max(f32::EPSILON))
+            });
+            match self.solutions.last_mut() {
+                // Same path, but a tighter guarantee
+                Some(last) if cost >= last.cost - 1e-4 => {
+                    last.bound = match (last.bound, bound) {
+                        (Some(a), Some(b)) => Some(a.min(b)),
+                        (a, b) => a.or(b),
+                    }
+                }
+                _ => {
+                    let path = self.path_to(goal);
+                    self.solutions.push(Solution {
+                        weight: self.weight,
+                        path,
+                        cost,
+                        bound,
+                        expansions: self.expansions,
+                    });
+                }
+            }
+        }
+
+        if self.weight <= 1.0 || (self.open_set.is_empty() && self.incons.is_empty()) {
+            self.done = true;
+            return;
+        }
+        self.weight = (self.weight - ARA_WEIGHT_STEP).max(1.0);
+        // Every f changes with the weight, so the heap is rebuilt from scratch
+        let reopened: Vec<Node> = self.open_set.drain().chain(self.incons.drain()).collect();
+        self.open.clear();
+        self.closed.clear();
+        for node in reopened {
+            self.push(heuristic, node);
+        }
+    }
+
+    // Older solutions as fading lines, the newest as tiles on top of the search state
+    pub fn draw(&self, maze: &Maze, cell_size: f32) {
+        let is_endpoint = |n: &Node| *n == maze.start || self.goals.contains(n);
+        for node in self.closed.iter().filter(|n| !is_endpoint(n)) {
+            maze.draw_tile(*node, cell_size, COLOR_PATH);
+        }
+        for node in self.open_set.iter().filter(|n| !is_endpoint(n)) {
+            maze.draw_tile(*node, cell_size, COLOR_OPEN);
+        }
+
+        let Some((latest, older)) = self.solutions.split_last() else {
+            return;
+        };
+        for node in latest.path.iter().filter(|n| !is_endpoint(n)) {
+            maze.draw_tile(*node, cell_size, GREEN);
+        }
+        let width = maze.view_cell_size(cell_size) / 4.0;
+        for (i, solution) in older.iter().enumerate() {
+            let alpha = 0.3 + 0.5 * (i + 1) as f32 / older.len() as f32;
+            let color = Color::new(1.0, 0.3, 0.6, alpha);
+            for pair in solution.path.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                if maze.visible(a.z) && maze.visible(b.z) {
+                    let (pa, pb) = (maze.center(a, cell_size), maze.center(b, cell_size));
+                    draw_line(pa.x, pa.y, pb.x, pb.y, width, color);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use macroquad::prelude::*;
+
+use crate::algorithm::AStarVisualizer;
+use crate::helper::gradient;
+use crate::heuristic::Heuristic;
+use crate::maze::Maze;
+use crate::node::Node;
+
+const TOLERANCE: f32 = 1e-3; // Float slack before a cell or edge counts as a violation
+
+// How far a heuristic can be trusted on this maze. True costs to the nearest goal come
+// from one Dijkstra run out of the goals, then each reachable cell is checked for
+// h > h* (inadmissible) and each move a -> b for h(a) > cost(a, b) + h(b) (inconsistent)
+pub struct HeuristicCheck {
+    pub heuristic_index: usize,
+    pub distance: HashMap<Node, f32>, // h*: true cost to the nearest goal
+    pub h: HashMap<Node, f32>,
+    pub overestimates: Vec<Node>,
+    pub inconsistent: Vec<(Node, Node)>,
+    pub worst: Option<(Node, f32)>, // Largest h / h*, where h* > 0
+}
+
+impl HeuristicCheck {
+    pub fn run(
+        maze: &Maze,
+        goals: &[Node],
+        heuristic_index: usize,
+        heuristic: &dyn Heuristic,
+    ) -> Self {
+        let distance = AStarVisualizer::distances(maze, goals);
+
+        let h: HashMap<Node, f32> = distance
+            .keys()
+            .map(|&n| {
+                let h = goals
+                    .iter()
+                    .map(|&g| heuristic.estimate(n, g))
+                    .fold(f32::INFINITY, f32::min);
+                (n, h)
+            })
+            .collect();
+
+        let mut check = Self {
+            heuristic_index,
+            overestimates: Vec::new(),
+            inconsistent: Vec::new(),
+            worst: None,
+            distance,
+            h,
+        };
+        for (&node, &true_cost) in &check.distance {
+            let h = check.h[&node];
+            if h > true_cost + TOLERANCE {
+                check.overestimates.push(node);
+            }
+            if true_cost > 0.0 && check.worst.is_none_or(|(_, r)| h / true_cost > r) {
+                check.worst = Some((node, h / true_cost));
+            }
+            for next in maze.moves(node) {
+                if let Some(&h_next) = check.h.get(&next)
+                    && h > maze.move_cost(node, next) + h_next + TOLERANCE
+                {
+                    check.inconsistent.push((node, next));
+                }
+            }
+        }
+        check
+    }
+
+    pub fn admissible(&self) -> bool {
+        self.overestimates.is_empty()
+    }
+
+    pub fn consistent(&self) -> bool {
+        self.inconsistent.is_empty()
+    }
+
+    // One line for the dashboard
+    pub fn summary(&self) -> String {
+        let cells = self.distance.len();
+        let admissible = if self.admissible() {
+            "admissible".to_string()
+        } else {
+            format!(
+                "overestimates at {}/{} cells",
+                self.overestimates.len(),
+                cells
+            )
+        };
+        let consistent = if self.consistent() {
+            "consistent".to_string()
+        } else {
+            format!("{} inconsistent moves", self.inconsistent.len())
+        };
+        let worst = self.worst.map_or(String::new(), |(node, ratio)| {
+            format!(
+                " | worst h/h* {:.2} at ({}, {}, {})",
+                ratio, node.x, node.y, node.z
+            )
+        });
+        format!("{} | {}{}", admissible, consistent, worst)
+    }
+
+    // Heatmap of h / h*: blue for a weak estimate, green for exact, red past the true
+    // cost. The start of every inconsistent move is outlined
+    pub fn draw(&self, maze: &Maze, cell_size: f32) {
+        for (&node, &true_cost) in &self.distance {
+            let h = self.h[&node];
+            let ratio = if true_cost > 0.0 {
+                h / true_cost
+            } else if h > TOLERANCE {
+                2.0
+            } else {
+                1.0
+            };
+            let mut color = gradient(ratio / 2.0);
+            color.a = 0.8;
+            maze.draw_tile(node, cell_size, color);
+        }
+        let thickness = (maze.view_cell_size(cell_size) / 8.0).max(1.0);
+        for (node, _) in &self.inconsistent {
+            maze.draw_tile_lines(*node, cell_size, thickness, MAGENTA);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_names_the_floor_of_the_worst_cell() {
+        let worst = Node::at(3, 5, 2);
+        let check = HeuristicCheck {
+            heuristic_index: 0,
+            distance: HashMap::from([(worst, 4.0)]),
+            h: HashMap::from([(worst, 6.0)]),
+            overestimates: vec![worst],
+            inconsistent: Vec::new(),
+            worst: Some((worst, 1.5)),
+        };
+        assert_eq!(
+            check.summary(),
+            "overestimates at 1/1 cells | consistent | worst h/h* 1.50 at (3, 5, 2)"
+        );
+    }
+}
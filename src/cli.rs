@@ -0,0 +1,182 @@
+use std::path::Path;
+
+use crate::config::AppConfig;
+use crate::constants::{CONFIG_FILE, RECORD_EVERY};
+use crate::session::SessionLog;
+
+pub const USAGE: &str = "\
+Usage: [--config <file.toml | file.json>] [--<setting> <value> ...]
+         [--scenario <file.json> | --replay <file.json> | --record <file.gif | directory> [--every N]]
+Settings: --width, --height (odd tiles), --cell-size (pixels), --steps-per-frame,
+          --algorithm (backtracker, prims, braid, eller, kruskal), --heuristic (1-9 or name),
+          --seed, --density (0-1), --step-delay (seconds), --topology (square, hex, polar),
+          --floors (1-3), --weave (true, false), --heuristic-weight (1-5),
+          --connectivity (4-way, 8-way), --landmarks (1-16), --landmark-selection,
+          --agent-speed (tiles/s), --obstacle-interval (seconds), --trail-length (0-1000)
+Config files are TOML, or a JSON object for .json, with the same keys as the flags
+('_' or '-'); tables and nested objects only group them";
+
+// What the command line asks for
+pub enum Command {
+    Run {
+        config: AppConfig,
+        scenario: Option<String>,
+        replay: Option<Box<SessionLog>>,
+    },
+    // Headless: no window is opened
+    Record {
+        config: AppConfig,
+        path: String,
+        every: usize,
+    },
+}
+
+// Settings come from the defaults, then the config file (`--config`, or CONFIG_FILE
+// when it exists), then any `--<setting>` flags. A replay runs on the config and
+// scenario it logged
+pub fn parse(args: &[String]) -> Result<Command, String> {
+    let mut config = AppConfig::default();
+    let config_path = match args.iter().position(|a| a == "--config") {
+        Some(i) => Some(args.get(i + 1).ok_or("--config needs a file")?.as_str()),
+        None => Path::new(CONFIG_FILE).exists().then_some(CONFIG_FILE),
+    };
+    if let Some(path) = config_path {
+        config
+            .load(path)
+            .map_err(|err| format!("Failed to load {}: {}", path, err))?;
+    }
+
+    let (mut scenario, mut replay, mut record) = (None, None, None);
+    let mut every = RECORD_EVERY;
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", flag))?;
+        match flag.as_str() {
+            "--config" => {}
+            "--scenario" => scenario = Some(value.clone()),
+            "--replay" => {
+                let log = SessionLog::load(value)
+                    .map_err(|err| format!("Failed to load {}: {}", value, err))?;
+                replay = Some(Box::new(log));
+            }
+            "--record" => record = Some(value.clone()),
+            "--every" => {
+                every = value
+                    .parse()
+                    .map_err(|_| "--every expects a number of steps".to_string())?
+            }
+            _ => match flag.strip_prefix("--") {
+                Some(key) => config.set(key, value)?,
+                None => return Err(format!("unexpected argument '{}'", flag)),
+            },
+        }
+    }
+
+    if let Some(path) = record {
+        return Ok(Command::Record {
+            config,
+            path,
+            every,
+        });
+    }
+    if let Some(log) = &replay {
+        config = log.config.clone();
+        scenario = None;
+    }
+    Ok(Command::Run {
+        config,
+        scenario,
+        replay,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn run_config(list: &[&str]) -> AppConfig {
+        match parse(&args(list)) {
+            Ok(Command::Run { config, .. }) => config,
+            _ => panic!("expected a run for {:?}", list),
+        }
+    }
+
+    fn error(list: &[&str]) -> String {
+        match parse(&args(list)) {
+            Err(err) => err,
+            Ok(_) => panic!("expected an error for {:?}", list),
+        }
+    }
+
+    fn config_file(name: &str, text: &str) -> String {
+        let path = std::env::temp_dir().join(format!("maze-cli-{}-{}", std::process::id(), name));
+        std::fs::write(&path, text).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn flags_win_over_the_config_file() {
+        let file = config_file("precedence.toml", "width = 41\nheight = 31\nseed = 5\n");
+        // Wherever --config appears, the file is read before any flag
+        for list in [
+            ["--config", &file, "--width", "51"],
+            ["--width", "51", "--config", &file],
+        ] {
+            let config = run_config(&list);
+            assert_eq!(config.grid_width, 51);
+            assert_eq!(config.grid_height, 31);
+            assert_eq!(config.seed, Some(5));
+        }
+        assert_eq!(run_config(&["--density", "0.2"]).density, 0.2);
+    }
+
+    #[test]
+    fn bad_values_are_reported() {
+        assert!(error(&["--width", "12"]).starts_with("'width' expects an odd size"));
+        assert_eq!(error(&["--colour", "red"]), "unknown setting 'colour'");
+        assert_eq!(
+            error(&["--every", "often", "--record", "out.gif"]),
+            "--every expects a number of steps"
+        );
+        assert_eq!(error(&["maze", "41"]), "unexpected argument 'maze'");
+        assert_eq!(error(&["--seed"]), "--seed needs a value");
+
+        let file = config_file("bad.toml", "density = 3\n");
+        let err = error(&["--config", &file, "--density", "0.5"]);
+        assert!(err.starts_with(&format!("Failed to load {}: ", file)));
+        assert!(err.contains("'density' expects 0-1"));
+    }
+
+    #[test]
+    fn config_needs_an_existing_file() {
+        assert_eq!(error(&["--config"]), "--config needs a file");
+        assert_eq!(
+            error(&["--width", "41", "--config"]),
+            "--config needs a file"
+        );
+        assert!(error(&["--config", "/nonexistent/maze.toml"]).starts_with("Failed to load"));
+    }
+
+    #[test]
+    fn record_takes_the_config_and_step_count() {
+        match parse(&args(&[
+            "--record", "out.gif", "--every", "3", "--width", "21",
+        ])) {
+            Ok(Command::Record {
+                config,
+                path,
+                every,
+            }) => {
+                assert_eq!((path.as_str(), every), ("out.gif", 3));
+                assert_eq!(config.grid_width, 21);
+            }
+            _ => panic!("expected a recording"),
+        }
+    }
+}
@@ -1,4 +1,15 @@
+use std::path::Path;
+use std::{fs, io};
+
+use macroquad::prelude::Rect;
+use serde_json::{Value, json};
+
 use crate::constants::*;
+use crate::generator::Algorithm;
+use crate::heuristic::Heuristics;
+use crate::landmarks::Selection;
+use crate::maze::Connectivity;
+use crate::topology::Topology;
 
 #[derive(Clone, Debug)]
 pub struct AppConfig {
@@ -8,7 +19,23 @@ pub struct AppConfig {
     pub screen_width: f32,
     pub screen_height: f32,
     pub ui_height: f32,
-    pub simulation_speed: usize,
+    pub simulation_speed: usize, // Generator / solver steps per frame...
+    pub step_delay: f64,         // ...or, when non-zero, seconds per step
+    pub time_scale: f32,         // Agent and obstacle clock
+    pub topology: Topology,
+    pub floors: usize,
+    pub weave: bool,
+    pub algorithm: Algorithm, // Current generator
+    pub heuristic: usize,     // Heuristic the app starts with
+    pub seed: Option<u64>,    // Fixed RNG seed; random when unset
+    pub density: f32,
+    pub heuristic_weight: f32,
+    pub connectivity: Connectivity,
+    pub agent_speed: f32, // Main agent; the crowd moves proportionally slower
+    pub obstacle_interval: f64,
+    pub trail_length: usize,
+    pub landmarks: usize, // Landmark count for the ALT heuristic
+    pub landmark_selection: Selection,
 }
 
 impl Default for AppConfig {
@@ -28,6 +55,530 @@ impl Default for AppConfig {
             screen_height,
             ui_height,
             simulation_speed: STEPS_PER_FRAME,
+            step_delay: STEP_DELAY_SEC,
+            time_scale: 1.0,
+            topology: Topology::Square,
+            floors: 1,
+            weave: false,
+            algorithm: Algorithm::RecursiveBacktracker,
+            heuristic: 0,
+            seed: None,
+            density: DENSITY,
+            heuristic_weight: 1.0,
+            connectivity: Connectivity::Four,
+            agent_speed: AGENT_SPEED,
+            obstacle_interval: OBSTACLE_INTERVAL_SEC,
+            trail_length: TRAIL_LENGTH,
+            landmarks: LANDMARK_COUNT,
+            landmark_selection: Selection::Farthest,
+        }
+    }
+}
+
+impl AppConfig {
+    // A TOML file or, for a .json file, a JSON object. Tables / nested objects only
+    // group settings: `[maze] width = 41` sets `width`
+    pub fn load(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        if path.extension().is_some_and(|e| e == "json") {
+            let value = serde_json::from_str(&text).map_err(|err| invalid(err.to_string()))?;
+            return self.apply_json(&value).map_err(invalid);
+        }
+        let table: toml::Table = text
+            .parse()
+            .map_err(|err: toml::de::Error| invalid(err.to_string().trim_end().to_string()))?;
+        self.apply_toml(&table).map_err(invalid)
+    }
+
+    pub fn apply_toml(&mut self, table: &toml::Table) -> Result<(), String> {
+        for (key, value) in table {
+            let text = match value {
+                toml::Value::Table(group) => {
+                    self.apply_toml(group)?;
+                    continue;
+                }
+                toml::Value::String(s) => s.clone(),
+                toml::Value::Integer(n) => n.to_string(),
+                toml::Value::Float(n) => n.to_string(),
+                toml::Value::Boolean(b) => b.to_string(),
+                _ => return Err(format!("'{}' must be a number, string or boolean", key)),
+            };
+            self.set(key, &text)?;
+        }
+        Ok(())
+    }
+
+    pub fn apply_json(&mut self, value: &Value) -> Result<(), String> {
+        let Value::Object(fields) = value else {
+            return Err("config must be a JSON object".to_string());
+        };
+        for (key, value) in fields {
+            let text = match value {
+                Value::Object(_) => {
+                    self.apply_json(value)?;
+                    continue;
+                }
+                Value::String(s) => s.clone(),
+                Value::Number(n) => n.to_string(),
+                Value::Bool(b) => b.to_string(),
+                _ => return Err(format!("'{}' must be a number, string or boolean", key)),
+            };
+            self.set(key, &text)?;
+        }
+        Ok(())
+    }
+
+    // Every setting a config file can hold, which covers everything the settings panel
+    // and the grid keys change, as a JSON object `apply_json` reads back
+    pub fn to_json(&self) -> Value {
+        let mut value = json!({
+            "width": self.grid_width,
+            "height": self.grid_height,
+            "cell-size": short(self.cell_size),
+            "steps-per-frame": self.simulation_speed,
+            "step-delay": self.step_delay,
+            "topology": self.topology.label().to_lowercase(),
+            "floors": self.floors,
+            "weave": self.weave,
+            "algorithm": self.algorithm.name(),
+            "heuristic": self.heuristic + 1,
+            "density": short(self.density),
+            "heuristic-weight": short(self.heuristic_weight),
+            "connectivity": self.connectivity.label(),
+            "landmarks": self.landmarks,
+            "landmark-selection": self.landmark_selection.label().to_lowercase(),
+            "agent-speed": short(self.agent_speed),
+            "obstacle-interval": self.obstacle_interval,
+            "trail-length": self.trail_length,
+        });
+        if let Some(seed) = self.seed {
+            value["seed"] = json!(seed.to_string());
+        }
+        value
+    }
+
+    // One setting by name, as written in a config file or after `--` on the command
+    // line; `cell_size` and `cell-size` are the same key
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let number = |what: &str| format!("'{}' expects {}, got '{}'", key, what, value);
+        match key.replace('_', "-").as_str() {
+            "width" | "height" => {
+                let size = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| (MIN_MAZE_SIZE..=MAX_MAZE_SIZE).contains(n) && n % 2 == 1)
+                    .ok_or_else(|| {
+                        number(&format!("an odd size {}-{}", MIN_MAZE_SIZE, MAX_MAZE_SIZE))
+                    })?;
+                if key == "width" {
+                    self.grid_width = size;
+                } else {
+                    self.grid_height = size;
+                }
+            }
+            "cell-size" => {
+                self.cell_size = value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|s| *s >= 1.0)
+                    .ok_or_else(|| number("pixels, at least 1"))?;
+            }
+            "steps-per-frame" => {
+                self.simulation_speed = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| (1..=MAX_STEPS_PER_FRAME).contains(n))
+                    .ok_or_else(|| number(&format!("1-{}", MAX_STEPS_PER_FRAME)))?;
+            }
+            // Seconds per step; 0 runs steps-per-frame steps every frame instead
+            "step-delay" => {
+                self.step_delay = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|d| (0.0..=MAX_STEP_DELAY_SEC).contains(d))
+                    .ok_or_else(|| number(&format!("0-{} seconds", MAX_STEP_DELAY_SEC)))?;
+            }
+            "topology" => {
+                self.topology = Topology::from_name(value, self.grid_width, self.grid_height)
+                    .ok_or_else(|| number("square, hex or polar"))?;
+            }
+            "floors" => {
+                self.floors = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| (1..=MAX_FLOORS).contains(n))
+                    .ok_or_else(|| number(&format!("1-{}", MAX_FLOORS)))?;
+            }
+            "weave" => self.weave = value.parse().map_err(|_| number("true or false"))?,
+            "algorithm" | "generator" => {
+                self.algorithm = Algorithm::from_name(value)
+                    .ok_or_else(|| number("backtracker, prims, braid, eller or kruskal"))?;
+            }
+            // The number key that selects it, or the name shown on the dashboard
+            "heuristic" => {
+                let heuristics = Heuristics::builtin();
+                self.heuristic = match value.parse::<usize>() {
+                    Ok(n) if (1..=heuristics.len()).contains(&n) => n - 1,
+                    _ => heuristics
+                        .position(value)
+                        .ok_or_else(|| number(&format!("1-{} or a name", heuristics.len())))?,
+                };
+            }
+            "seed" => self.seed = Some(value.parse().map_err(|_| number("a whole number"))?),
+            "density" => {
+                self.density = value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|d| (0.0..=1.0).contains(d))
+                    .ok_or_else(|| number("0-1"))?;
+            }
+            "heuristic-weight" | "weight" => {
+                self.heuristic_weight = value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|w| (1.0..=MAX_HEURISTIC_WEIGHT).contains(w))
+                    .ok_or_else(|| number(&format!("1-{}", MAX_HEURISTIC_WEIGHT)))?;
+            }
+            "connectivity" => {
+                self.connectivity =
+                    Connectivity::from_name(value).ok_or_else(|| number("4-way or 8-way"))?;
+            }
+            "landmarks" => {
+                self.landmarks = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| (1..=MAX_LANDMARKS).contains(n))
+                    .ok_or_else(|| number(&format!("1-{}", MAX_LANDMARKS)))?;
+            }
+            "landmark-selection" => {
+                self.landmark_selection =
+                    Selection::from_name(value).ok_or_else(|| number("farthest or random"))?;
+            }
+            "agent-speed" => {
+                let (min, max) = AGENT_SPEED_RANGE;
+                self.agent_speed = value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|s| (min..=max).contains(s))
+                    .ok_or_else(|| number(&format!("{}-{} tiles per second", min, max)))?;
+            }
+            "obstacle-interval" => {
+                let (min, max) = OBSTACLE_INTERVAL_RANGE;
+                self.obstacle_interval = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|s| (min..=max).contains(s))
+                    .ok_or_else(|| number(&format!("{}-{} seconds", min, max)))?;
+            }
+            "trail-length" => {
+                self.trail_length = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| *n <= MAX_TRAIL_LENGTH)
+                    .ok_or_else(|| number(&format!("0-{}", MAX_TRAIL_LENGTH)))?;
+            }
+            _ => return Err(format!("unknown setting '{}'", key)),
+        }
+        // A polar grid's ring count follows the grid size, whichever is set last
+        if let Topology::Polar { .. } = self.topology {
+            self.topology = Topology::polar(self.grid_width, self.grid_height);
+        }
+        self.fit_window();
+        Ok(())
+    }
+
+    // Window just big enough for the grid at the configured cell size
+    fn fit_window(&mut self) {
+        self.screen_width = self.topology.pixel_width(self.grid_width, self.cell_size);
+        self.screen_height =
+            self.topology.pixel_height(self.grid_height, self.cell_size) + self.ui_height;
+    }
+
+    // Screen area above the dashboard where the maze is shown
+    pub fn maze_area(&self) -> Rect {
+        Rect::new(
+            0.0,
+            0.0,
+            self.screen_width,
+            self.screen_height - self.ui_height,
+        )
+    }
+
+    // Largest cell size that shows the whole grid in the maze area
+    pub fn fit_cell_size(&mut self) {
+        let area = self.maze_area();
+        let width = self.topology.pixel_width(self.grid_width, 1.0);
+        let height = self.topology.pixel_height(self.grid_height, 1.0);
+        self.cell_size = (area.w / width).min(area.h / height);
+    }
+
+    pub fn speed_of(&self, is_main: bool) -> f32 {
+        if is_main {
+            self.agent_speed
+        } else {
+            self.agent_speed * CROWD_AGENT_SPEED / AGENT_SPEED
+        }
+    }
+
+    // Doubles the stepping speed: first by shortening the delay, then by batching steps
+    pub fn speed_up(&mut self) {
+        if self.step_delay > MIN_STEP_DELAY_SEC {
+            self.step_delay /= 2.0;
+        } else if self.step_delay > 0.0 {
+            self.step_delay = 0.0;
+        } else {
+            self.simulation_speed = (self.simulation_speed * 2).min(MAX_STEPS_PER_FRAME);
         }
     }
+
+    pub fn speed_down(&mut self) {
+        if self.simulation_speed > 1 {
+            self.simulation_speed /= 2;
+        } else if self.step_delay == 0.0 {
+            self.step_delay = MIN_STEP_DELAY_SEC;
+        } else {
+            self.step_delay = (self.step_delay * 2.0).min(MAX_STEP_DELAY_SEC);
+        }
+    }
+
+    pub fn speed_label(&self) -> String {
+        if self.step_delay > 0.0 {
+            format!("{:.3}s/step", self.step_delay)
+        } else {
+            format!("{}/frame", self.simulation_speed)
+        }
+    }
+
+    // Steps through TIME_SCALES; 0 freezes agents and obstacles
+    pub fn scale_time(&mut self, faster: bool) {
+        let i = TIME_SCALES
+            .iter()
+            .position(|&s| s >= self.time_scale)
+            .unwrap_or(TIME_SCALES.len() - 1);
+        let i = if faster {
+            (i + 1).min(TIME_SCALES.len() - 1)
+        } else {
+            i.saturating_sub(1)
+        };
+        self.time_scale = TIME_SCALES[i];
+    }
+}
+
+// f32 through its shortest decimal, so 0.6 isn't logged as 0.6000000238418579
+fn short(v: f32) -> Value {
+    json!(v.to_string().parse::<f64>().unwrap_or(v as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A config file of its own per test, as tests run in parallel
+    fn write_temp(name: &str, text: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("maze-{}-{}", std::process::id(), name));
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    fn load(name: &str, text: &str) -> io::Result<AppConfig> {
+        let mut config = AppConfig::default();
+        config.load(write_temp(name, text))?;
+        Ok(config)
+    }
+
+    #[test]
+    fn load_reads_toml_tables_and_strings() {
+        let config = load(
+            "tables.toml",
+            r#"
+# Comment lines, and a '#' inside a string
+width = 41
+seed = "18446744073709551615"  # past TOML's integer range
+view = { cell_size = 4.5 }
+
+[generator]
+algorithm = 'kruskal'
+density = 0.25
+
+[search]
+heuristic = """
+Euclidean"""
+landmark-selection = "random"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.grid_width, 41);
+        assert_eq!(config.seed, Some(u64::MAX));
+        assert_eq!(config.cell_size, 4.5);
+        assert_eq!(config.algorithm, Algorithm::Kruskal);
+        assert_eq!(config.density, 0.25);
+        assert_eq!(
+            Heuristics::builtin().get(config.heuristic).name(),
+            "Euclidean"
+        );
+        assert_eq!(config.landmark_selection, Selection::Random);
+    }
+
+    #[test]
+    fn load_reads_json_objects() {
+        let config = load(
+            "nested.json",
+            r#"{"height": 21, "search": {"heuristic": 3, "landmarks": 2}}"#,
+        )
+        .unwrap();
+        assert_eq!(config.grid_height, 21);
+        assert_eq!(config.heuristic, 2);
+        assert_eq!(config.landmarks, 2);
+    }
+
+    #[test]
+    fn load_reports_bad_files() {
+        let err = |name: &str, text: &str| load(name, text).unwrap_err().to_string();
+        assert!(err("syntax.toml", "width = \n").contains("line 1"));
+        assert!(err("array.toml", "width = [41]").contains("'width' must be a number"));
+        assert!(err("unknown.toml", "colour = 3").contains("unknown setting 'colour'"));
+        assert!(err("range.toml", "[maze]\nheight = 8").contains("'height' expects an odd size"));
+        assert!(err("list.json", "[1, 2]").contains("must be a JSON object"));
+        assert!(err("trailing.json", r#"{"width": 41,}"#).contains("line 1"));
+    }
+
+    #[test]
+    fn set_checks_each_value() {
+        let mut config = AppConfig::default();
+        let bad = [
+            ("width", "42"),
+            ("width", "9"),
+            ("height", "1003"),
+            ("cell-size", "0.5"),
+            ("steps-per-frame", "0"),
+            ("algorithm", "aldous"),
+            ("heuristic", "0"),
+            ("heuristic", "Nonexistent"),
+            ("seed", "-1"),
+            ("density", "1.5"),
+            ("density", "NaN"),
+            ("landmarks", "0"),
+            ("landmark-selection", "nearest"),
+            ("speed", "1"),
+            ("step-delay", "2"),
+            ("topology", "triangle"),
+            ("floors", "0"),
+            ("floors", "4"),
+            ("weave", "yes"),
+            ("weight", "0.5"),
+            ("connectivity", "6-way"),
+            ("agent-speed", "0"),
+            ("obstacle-interval", "10"),
+            ("trail-length", "1001"),
+        ];
+        for (key, value) in bad {
+            assert!(config.set(key, value).is_err(), "{} = {}", key, value);
+        }
+        assert_eq!(
+            format!("{:?}", config),
+            format!("{:?}", AppConfig::default())
+        );
+
+        config.set("steps_per_frame", "8").unwrap();
+        config.set("generator", "Eller").unwrap();
+        config.set("heuristic", "2").unwrap();
+        assert_eq!(config.simulation_speed, 8);
+        assert_eq!(config.algorithm, Algorithm::Eller);
+        assert_eq!(config.heuristic, 1);
+        assert_eq!(
+            config.set("width", "x").unwrap_err(),
+            format!(
+                "'width' expects an odd size {}-{}, got 'x'",
+                MIN_MAZE_SIZE, MAX_MAZE_SIZE
+            )
+        );
+    }
+
+    // Everything away from the defaults, as the panel and the grid keys can leave it
+    fn changed() -> AppConfig {
+        let mut config = AppConfig {
+            grid_width: 61,
+            grid_height: 41,
+            cell_size: 7.5,
+            simulation_speed: 16,
+            step_delay: 0.05,
+            floors: 2,
+            weave: true,
+            algorithm: Algorithm::Prims,
+            heuristic: 4,
+            seed: Some(u64::MAX),
+            density: 0.6,
+            heuristic_weight: 2.3,
+            connectivity: Connectivity::Eight,
+            agent_speed: 6.5,
+            obstacle_interval: 0.3,
+            trail_length: 40,
+            landmarks: 9,
+            landmark_selection: Selection::Random,
+            ..AppConfig::default()
+        };
+        config.topology = Topology::polar(config.grid_width, config.grid_height);
+        config.fit_window();
+        config
+    }
+
+    #[test]
+    fn json_keeps_every_setting() {
+        let config = changed();
+        let mut back = AppConfig::default();
+        back.apply_json(&config.to_json()).unwrap();
+        assert_eq!(format!("{:?}", back), format!("{:?}", config));
+
+        let text = config.to_json().to_string();
+        assert!(text.contains(r#""density":0.6,"#), "{}", text);
+        assert!(text.contains(r#""heuristic-weight":2.3,"#), "{}", text);
+    }
+
+    #[test]
+    fn toml_takes_the_same_keys() {
+        let mut back = AppConfig::default();
+        let text = r#"
+width = 61
+height = 41
+cell-size = 7.5
+steps-per-frame = 16
+step-delay = 0.05
+topology = "polar"
+floors = 2
+weave = true
+algorithm = "prims"
+heuristic = 5
+seed = "18446744073709551615"
+density = 0.6
+weight = 2.3
+connectivity = "8"
+landmarks = 9
+landmark-selection = "random"
+agent-speed = 6.5
+obstacle-interval = 0.3
+trail-length = 40
+"#;
+        back.apply_toml(&text.parse().unwrap()).unwrap();
+        assert_eq!(format!("{:?}", back), format!("{:?}", changed()));
+    }
+
+    #[test]
+    fn polar_rings_follow_the_grid_size() {
+        let mut config = AppConfig::default();
+        config.set("topology", "polar").unwrap();
+        config.set("width", "201").unwrap();
+        config.set("height", "201").unwrap();
+        assert_eq!(config.topology, Topology::polar(201, 201));
+    }
+
+    #[test]
+    fn set_resizes_the_window() {
+        let mut config = AppConfig::default();
+        config.set("cell-size", "2").unwrap();
+        config.set("width", "101").unwrap();
+        assert_eq!(config.screen_width, 202.0);
+    }
 }
@@ -6,26 +6,29 @@ use std::collections::HashMap;
 
 use macroquad::prelude::*;
 
-use crate::heuristic::*;
 use crate::node::Node;
 
-type HeuristicFn = fn(Node, Node) -> f32;
-
-pub const HEURISTIC: &[(KeyCode, HeuristicFn, &str)] = &[
-    (KeyCode::Key1, manhattan, "Manhattan"),
-    (KeyCode::Key2, euclidean, "Euclidean"),
-    (KeyCode::Key3, uniform_cost, "Uniform Cost Search"),
-    (KeyCode::Key4, euclidean_squared, "Euclidean Squared"),
-    (KeyCode::Key5, weighted_manhattan, "Weighted Manhattan"),
-    (
-        KeyCode::Key6,
-        manhattan_tiebreaker,
-        "Manhattan with Tiebreaker",
-    ),
+// Selects heuristic 1-10 in registration order (see Heuristics)
+pub const HEURISTIC_KEYS: [KeyCode; 10] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Key0,
 ];
 
+pub const DEFAULT_FORMULA: &str = "abs(dx) + abs(dy) + abs(dz)"; // Shown the first time F is pressed
+
 pub const MAZE_HEIGHT: usize = 51;
 pub const MAZE_WIDTH: usize = 2 * MAZE_HEIGHT - 1;
+// Range offered by the settings panel
+pub const MIN_MAZE_SIZE: usize = 11;
+pub const MAX_MAZE_SIZE: usize = 1001;
 
 pub const UI_HEIGHT: i32 = 180;
 
@@ -36,13 +39,68 @@ pub const WINDOW_HEIGHT: i32 = MAZE_HEIGHT as i32 * PIXEL_PER_TILE + UI_HEIGHT;
 
 pub const TILE_SIZE: f32 = PIXEL_PER_TILE as f32;
 
+pub const MIN_ZOOM: f32 = 0.02;
+pub const MAX_ZOOM: f32 = 16.0;
+pub const ZOOM_STEP: f32 = 1.2; // Per mouse wheel notch
+pub const PAN_THRESHOLD: f32 = 4.0; // Pixels a middle drag moves before it pans
+pub const OVERLAY_TEXT_MIN_CELL: f32 = 24.0; // On-screen cell size before g/h/f are printed
+
 pub const STEP_DELAY_SEC: f64 = 0.0;
 pub const STEPS_PER_FRAME: usize = 100;
-pub const MAZE_GEN_STEPS_PER_FRAME: usize = 100;
+pub const MAX_STEPS_PER_FRAME: usize = 4096;
+pub const MIN_STEP_DELAY_SEC: f64 = 0.025; // Slowing down past 1 step per frame starts here
+pub const MAX_STEP_DELAY_SEC: f64 = 1.6;
+pub const TIME_SCALES: [f32; 7] = [0.0, 0.25, 0.5, 1.0, 2.0, 4.0, 8.0]; // Agents & obstacles
 
 pub const DENSITY: f32 = 0.6;
+pub const MAX_HEURISTIC_WEIGHT: f32 = 5.0;
+pub const WEIGHT_STEP: f32 = 0.25; // Per , / . key press
+pub const ARA_WEIGHT_STEP: f32 = 0.5; // Weight dropped between ARA* rounds
+pub const LANDMARK_COUNT: usize = 4;
+pub const MAX_LANDMARKS: usize = 16;
+
+// Tiles per second; crowd agents keep the same ratio when the speed is changed
+pub const AGENT_SPEED: f32 = 4.0;
+pub const CROWD_AGENT_SPEED: f32 = 3.0;
+pub const AGENT_SPEED_RANGE: (f32, f32) = (0.5, 20.0);
+pub const OBSTACLE_INTERVAL_SEC: f64 = 1.0; // Between obstacle moves
+pub const OBSTACLE_INTERVAL_RANGE: (f64, f64) = (0.1, 5.0);
+pub const TRAIL_LENGTH: usize = 150; // Points kept per agent
+pub const MAX_TRAIL_LENGTH: usize = 1000;
+
+// Chance that a generator climbs to another floor while it could stay on the current one
+pub const STAIR_CHANCE: f32 = 0.05;
+pub const MAX_FLOORS: usize = 3;
+
+// Share of lattice cells Kruskal tries to turn into crossings in weave mode
+pub const WEAVE_DENSITY: f32 = 0.3;
+
+pub const CONFIG_FILE: &str = "config.toml"; // Read at startup when present
+pub const MAZE_FILE: &str = "maze.txt";
+pub const SCENARIO_FILE: &str = "scenario.json";
+pub const SESSION_FILE: &str = "session.json";
+pub const MAX_SESSION_FRAMES: usize = 216_000; // An hour at 60 fps; later input isn't logged
+pub const EXPORT_SVG_FILE: &str = "maze.svg";
+pub const EXPORT_PNG_FILE: &str = "maze.png";
+// Pixels per tile in exported figures, independent of the window
+pub const EXPORT_CELL_SIZE: f32 = 16.0;
+
+// Headless recording (--record): frame spacing in steps, tile size and frame timing
+// in hundredths of a second; the end of each phase is held a little longer
+pub const RECORD_EVERY: usize = 10;
+pub const RECORD_CELL_SIZE: f32 = 8.0;
+pub const RECORD_FRAME_DELAY: u16 = 4;
+pub const RECORD_HOLD_DELAY: u16 = 150;
+pub const RECORD_GIF_SPEED: i32 = 10; // Quantizer speed, 1 to 30; frames of up to 256 colors stay exact
+// Mask shapes tried in order: dark pixels / non-space characters are inside the maze
+pub const MASK_FILES: &[&str] = &["mask.png", "mask.txt"];
+
+// Tours with up to this many goals are solved exactly (Held-Karp)
+pub const TOUR_EXACT_LIMIT: usize = 10;
 
 pub const COLOR_PATH: Color = Color::new(0.1, 0.8, 1.0, 0.5);
+pub const COLOR_OPEN: Color = Color::new(1.0, 0.7, 0.1, 0.7);
+pub const COLOR_CURRENT: Color = MAGENTA;
 pub const WALL_COLOR: Color = BLACK;
 pub const PATH_COLOR: Color = WHITE;
 pub const VISITED_COLOR: Color = Color::new(0.8, 0.8, 1.0, 1.0);
@@ -0,0 +1,478 @@
+use macroquad::prelude::*;
+
+use crate::input::FrameInput;
+use crate::maze::{Maze, Tile};
+use crate::node::Node;
+
+#[derive(Clone, Copy, Debug)]
+pub struct CellEdit {
+    pub node: Node,
+    pub before: Tile,
+    pub after: Tile,
+}
+
+#[derive(Clone, Debug)]
+pub enum EditAction {
+    Paint(Vec<CellEdit>),
+    MoveStart {
+        from: Node,
+        to: Node,
+        replaced: Tile,
+    },
+    MoveGoal {
+        from: Node,
+        to: Node,
+        replaced: Tile,
+        extra: Option<usize>, // Where `to` sat in `extra_goals`, when the goal landed on one
+    },
+    ToggleGoal {
+        node: Node,
+        replaced: Tile,
+        added: bool,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Endpoint {
+    Start,
+    Goal,
+}
+
+pub struct Editor {
+    pub active: bool,
+    dragging: Option<Endpoint>,
+    stroke: Option<Vec<CellEdit>>,
+    last_cell: Option<Node>,
+    undo_stack: Vec<EditAction>,
+    redo_stack: Vec<EditAction>,
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Self {
+            active: false,
+            dragging: None,
+            stroke: None,
+            last_cell: None,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+        }
+    }
+
+    pub fn clear_history(&mut self) {
+        self.dragging = None;
+        self.stroke = None;
+        self.last_cell = None;
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+
+    pub fn undo_len(&self) -> usize {
+        self.undo_stack.len()
+    }
+
+    pub fn redo_len(&self) -> usize {
+        self.redo_stack.len()
+    }
+
+    // Returns true when the maze changed and paths need to be replanned
+    pub fn handle_input(&mut self, maze: &mut Maze, input: &FrameInput) -> bool {
+        let ctrl = input.ctrl();
+        let shift = input.shift();
+
+        if ctrl && input.key_pressed(KeyCode::Z) {
+            return if shift {
+                self.redo(maze)
+            } else {
+                self.undo(maze)
+            };
+        }
+        if ctrl && input.key_pressed(KeyCode::Y) {
+            return self.redo(maze);
+        }
+
+        let hovered = input.cursor;
+
+        if let Some(node) = hovered {
+            if input.key_pressed(KeyCode::S) {
+                return self.place_start(maze, node);
+            }
+            if input.key_pressed(KeyCode::G) {
+                return self.place_goal(maze, node);
+            }
+            if input.key_pressed(KeyCode::X) {
+                return self.toggle_extra_goal(maze, node);
+            }
+        }
+
+        // Dragging the start or goal tile moves it instead of painting
+        if let Some(endpoint) = self.dragging {
+            if input.button_down(MouseButton::Left) {
+                return false;
+            }
+            self.dragging = None;
+            return match (endpoint, hovered) {
+                (Endpoint::Start, Some(node)) => self.place_start(maze, node),
+                (Endpoint::Goal, Some(node)) => self.place_goal(maze, node),
+                _ => false,
+            };
+        }
+        if input.button_pressed(MouseButton::Left) && self.stroke.is_none() {
+            if hovered == Some(maze.start) {
+                self.dragging = Some(Endpoint::Start);
+                return false;
+            }
+            if hovered == Some(maze.goal) {
+                self.dragging = Some(Endpoint::Goal);
+                return false;
+            }
+        }
+
+        // Left drag paints walls, right drag erases them
+        let brush = if input.button_down(MouseButton::Left) {
+            Some(Tile::Wall)
+        } else if input.button_down(MouseButton::Right) {
+            Some(Tile::Path)
+        } else {
+            None
+        };
+
+        match brush {
+            Some(tile) => {
+                if self.stroke.is_none() {
+                    self.stroke = Some(Vec::new());
+                    self.last_cell = None;
+                }
+                if let Some(node) = hovered {
+                    let from = self.last_cell.filter(|c| c.z == node.z).unwrap_or(node);
+                    for cell in Self::line_cells(from, node) {
+                        self.paint(maze, cell, tile);
+                    }
+                    self.last_cell = Some(node);
+                }
+                false
+            }
+            None => self.end_stroke(),
+        }
+    }
+
+    // Cells between two drag samples so fast mouse moves leave no gaps
+    fn line_cells(from: Node, to: Node) -> Vec<Node> {
+        let dx = to.x as isize - from.x as isize;
+        let dy = to.y as isize - from.y as isize;
+        let steps = dx.abs().max(dy.abs()).max(1);
+
+        (0..=steps)
+            .map(|i| {
+                let t = i as f32 / steps as f32;
+                Node::at(
+                    (from.x as f32 + dx as f32 * t).round() as usize,
+                    (from.y as f32 + dy as f32 * t).round() as usize,
+                    to.z,
+                )
+            })
+            .collect()
+    }
+
+    fn paint(&mut self, maze: &mut Maze, node: Node, tile: Tile) {
+        let before = maze[node];
+        if before == tile || !matches!(before, Tile::Path | Tile::Wall) {
+            return;
+        }
+
+        maze[node] = tile;
+        if let Some(stroke) = &mut self.stroke {
+            stroke.push(CellEdit {
+                node,
+                before,
+                after: tile,
+            });
+        }
+    }
+
+    fn end_stroke(&mut self) -> bool {
+        self.last_cell = None;
+        match self.stroke.take() {
+            Some(cells) if !cells.is_empty() => {
+                self.push(EditAction::Paint(cells));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Markers only go on plain floor: leaving a wall, tunnel or bridge would write floor
+    // back over it, carving a hole or cutting a crossing
+    fn open(maze: &Maze, node: Node) -> bool {
+        matches!(maze[node], Tile::Path | Tile::Stairs)
+    }
+
+    pub fn place_start(&mut self, maze: &mut Maze, node: Node) -> bool {
+        if node == maze.start || maze.goals().contains(&node) || !Self::open(maze, node) {
+            return false;
+        }
+
+        let action = EditAction::MoveStart {
+            from: maze.start,
+            to: node,
+            replaced: maze[node],
+        };
+        maze.set_start(node);
+        self.push(action);
+        true
+    }
+
+    pub fn place_goal(&mut self, maze: &mut Maze, node: Node) -> bool {
+        let extra = maze.extra_goals.contains(&node);
+        if node == maze.start || node == maze.goal || !(extra || Self::open(maze, node)) {
+            return false;
+        }
+
+        let action = EditAction::MoveGoal {
+            from: maze.goal,
+            to: node,
+            replaced: maze[node],
+            extra: maze.extra_goals.iter().position(|&g| g == node),
+        };
+        maze.set_goal(node);
+        self.push(action);
+        true
+    }
+
+    pub fn toggle_extra_goal(&mut self, maze: &mut Maze, node: Node) -> bool {
+        let added = !maze.extra_goals.contains(&node);
+        if node == maze.start || node == maze.goal || (added && !Self::open(maze, node)) {
+            return false;
+        }
+
+        let action = EditAction::ToggleGoal {
+            node,
+            replaced: maze[node],
+            added,
+        };
+        if added {
+            maze.add_extra_goal(node);
+        } else {
+            maze.remove_extra_goal(node);
+        }
+        self.push(action);
+        true
+    }
+
+    fn push(&mut self, action: EditAction) {
+        self.undo_stack.push(action);
+        self.redo_stack.clear();
+    }
+
+    pub fn undo(&mut self, maze: &mut Maze) -> bool {
+        let Some(action) = self.undo_stack.pop() else {
+            return false;
+        };
+
+        match &action {
+            EditAction::Paint(cells) => {
+                for edit in cells.iter().rev() {
+                    maze[edit.node] = edit.before;
+                }
+            }
+            EditAction::MoveStart { from, to, replaced } => {
+                maze.set_start(*from);
+                maze[*to] = *replaced;
+            }
+            EditAction::MoveGoal {
+                from,
+                to,
+                replaced,
+                extra,
+            } => {
+                maze.set_goal(*from);
+                // `set_goal` dropped the extra goal it landed on; put it back in place
+                match extra {
+                    Some(i) => {
+                        maze.extra_goals.insert(*i, *to);
+                        maze[*to] = Tile::Goal;
+                    }
+                    None => maze[*to] = *replaced,
+                }
+            }
+            EditAction::ToggleGoal {
+                node,
+                replaced,
+                added,
+            } => {
+                if *added {
+                    maze.remove_extra_goal(*node);
+                    maze[*node] = *replaced;
+                } else {
+                    maze.add_extra_goal(*node);
+                }
+            }
+        }
+
+        self.redo_stack.push(action);
+        true
+    }
+
+    pub fn redo(&mut self, maze: &mut Maze) -> bool {
+        let Some(action) = self.redo_stack.pop() else {
+            return false;
+        };
+
+        match &action {
+            EditAction::Paint(cells) => {
+                for edit in cells {
+                    maze[edit.node] = edit.after;
+                }
+            }
+            EditAction::MoveStart { to, .. } => maze.set_start(*to),
+            EditAction::MoveGoal { to, .. } => maze.set_goal(*to),
+            EditAction::ToggleGoal { node, added, .. } => {
+                if *added {
+                    maze.add_extra_goal(*node);
+                } else {
+                    maze.remove_extra_goal(*node);
+                }
+            }
+        }
+
+        self.undo_stack.push(action);
+        true
+    }
+
+    pub fn draw(&self, maze: &Maze, cell_size: f32, hovered: Option<Node>) {
+        if let Some(node) = hovered {
+            let outline = match self.dragging {
+                Some(Endpoint::Start) => GREEN,
+                Some(Endpoint::Goal) => RED,
+                None => YELLOW,
+            };
+            maze.draw_tile_lines(node, cell_size, 2.0, outline);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WALL: Node = Node { x: 2, y: 2, z: 0 };
+    const TUNNEL: Node = Node { x: 3, y: 3, z: 0 };
+    const BRIDGE: Node = Node { x: 4, y: 4, z: 0 };
+
+    // Open 7x7 room with one wall, one crossing and one staircase inside
+    fn room() -> Maze {
+        let mut maze = Maze::new(9, 9);
+        for y in 1..8 {
+            for x in 1..8 {
+                maze[Node::at(x, y, 0)] = Tile::Path;
+            }
+        }
+        maze[WALL] = Tile::Wall;
+        maze[TUNNEL] = Tile::Tunnel;
+        maze[BRIDGE] = Tile::Bridge;
+        maze.stairs.insert(Node::at(5, 1, 0));
+        maze[Node::at(5, 1, 0)] = Tile::Stairs;
+        maze.set_start(Node::at(1, 1, 0));
+        maze.set_goal(Node::at(7, 7, 0));
+        maze
+    }
+
+    fn tiles(maze: &Maze) -> Vec<Tile> {
+        maze.layers.concat().concat()
+    }
+
+    #[test]
+    fn markers_refuse_walls_and_crossings() {
+        let mut maze = room();
+        let before = tiles(&maze);
+        let mut editor = Editor::new();
+        for node in [WALL, TUNNEL, BRIDGE] {
+            assert!(!editor.place_start(&mut maze, node));
+            assert!(!editor.place_goal(&mut maze, node));
+            assert!(!editor.toggle_extra_goal(&mut maze, node));
+        }
+        assert_eq!(tiles(&maze), before);
+        assert_eq!(editor.undo_len(), 0);
+    }
+
+    #[test]
+    fn start_move_undoes_and_redoes() {
+        let mut maze = room();
+        let before = tiles(&maze);
+        let mut editor = Editor::new();
+        let to = Node::at(2, 1, 0);
+
+        assert!(editor.place_start(&mut maze, to));
+        assert_eq!((maze.start, maze[to]), (to, Tile::Start));
+        assert_eq!(maze[Node::at(1, 1, 0)], Tile::Path);
+
+        assert!(editor.undo(&mut maze));
+        assert_eq!(maze.start, Node::at(1, 1, 0));
+        assert_eq!(tiles(&maze), before);
+
+        assert!(editor.redo(&mut maze));
+        assert_eq!((maze.start, maze[to]), (to, Tile::Start));
+    }
+
+    #[test]
+    fn start_leaving_stairs_puts_them_back() {
+        let mut maze = room();
+        let stairs = Node::at(5, 1, 0);
+        let mut editor = Editor::new();
+
+        assert!(editor.place_start(&mut maze, stairs));
+        assert!(editor.place_start(&mut maze, Node::at(6, 1, 0)));
+        assert_eq!(maze[stairs], Tile::Stairs);
+        assert!(editor.undo(&mut maze));
+        assert_eq!(maze[stairs], Tile::Start);
+        assert!(editor.undo(&mut maze));
+        assert_eq!(maze[stairs], Tile::Stairs);
+    }
+
+    #[test]
+    fn goal_onto_extra_goal_restores_it_on_undo() {
+        let mut maze = room();
+        let mut editor = Editor::new();
+        let (a, b) = (Node::at(3, 5, 0), Node::at(5, 5, 0));
+        assert!(editor.toggle_extra_goal(&mut maze, a));
+        assert!(editor.toggle_extra_goal(&mut maze, b));
+        let before = tiles(&maze);
+
+        assert!(editor.place_goal(&mut maze, a));
+        assert_eq!(maze.goals(), vec![a, b]);
+        assert_eq!(maze[Node::at(7, 7, 0)], Tile::Path);
+
+        assert!(editor.undo(&mut maze));
+        assert_eq!(maze.goals(), vec![Node::at(7, 7, 0), a, b]);
+        assert_eq!(tiles(&maze), before);
+
+        assert!(editor.redo(&mut maze));
+        assert_eq!(maze.goals(), vec![a, b]);
+    }
+
+    #[test]
+    fn extra_goal_toggle_undoes_and_redoes() {
+        let mut maze = room();
+        let before = tiles(&maze);
+        let mut editor = Editor::new();
+        let node = Node::at(3, 5, 0);
+
+        assert!(editor.toggle_extra_goal(&mut maze, node));
+        assert!(editor.toggle_extra_goal(&mut maze, node));
+        assert_eq!(tiles(&maze), before);
+
+        assert!(editor.undo(&mut maze));
+        assert_eq!(
+            (maze.extra_goals.clone(), maze[node]),
+            (vec![node], Tile::Goal)
+        );
+        assert!(editor.undo(&mut maze));
+        assert!(maze.extra_goals.is_empty());
+        assert_eq!(tiles(&maze), before);
+
+        assert!(editor.redo(&mut maze));
+        assert!(editor.redo(&mut maze));
+        assert_eq!(tiles(&maze), before);
+        assert!(!editor.redo(&mut maze));
+    }
+}
@@ -0,0 +1,344 @@
+use macroquad::prelude::*;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::{fs, io};
+
+use crate::agent::Agent;
+use crate::algorithm::AStarVisualizer;
+use crate::constants::COLOR_PATH;
+use crate::maze::Maze;
+use crate::node::Node;
+use crate::obstacle::DynamicObstacle;
+use crate::topology::Topology;
+
+enum Shape {
+    Polygon(Vec<Vec2>),
+    Circle(Vec2, f32),
+    Line(Vec2, Vec2, f32),
+}
+
+struct Layer {
+    name: &'static str,
+    shapes: Vec<(Shape, Color)>,
+}
+
+// Resolution-independent snapshot of the scene: every floor side by side at full size,
+// kept in layers so the SVG can be edited group by group
+pub struct Figure {
+    width: f32,
+    height: f32,
+    layers: Vec<Layer>,
+}
+
+impl Figure {
+    pub fn capture(
+        maze: &Maze,
+        solver: Option<&AStarVisualizer>,
+        agents: &[Agent],
+        obstacles: &[DynamicObstacle],
+        cell_size: f32,
+    ) -> Self {
+        let panel = maze.topology.pixel_width(maze.width, cell_size);
+        let origin = |z: usize| vec2(z as f32 * panel, 0.0);
+        let point = |p: Vec3| {
+            origin(p.z.round().max(0.0) as usize) + maze.topology.center(p.x, p.y, cell_size)
+        };
+        let tile = |node: Node| {
+            let corner = origin(node.z);
+            Shape::Polygon(
+                maze.topology
+                    .tile_outline(node, cell_size)
+                    .into_iter()
+                    .map(|p| corner + p)
+                    .collect(),
+            )
+        };
+
+        let mut walls = Vec::new();
+        for z in 0..maze.floors {
+            if let Topology::Polar { .. } = maze.topology {
+                let middle = panel / 2.0;
+                walls.push((
+                    Shape::Circle(origin(z) + vec2(middle, middle), 2.5 * cell_size),
+                    PURPLE,
+                ));
+            }
+            for y in 0..maze.height {
+                for x in 0..maze.width {
+                    let node = Node::at(x, y, z);
+                    if maze.visible_tile(node) {
+                        walls.push((tile(node), maze[node].color()));
+                    }
+                }
+            }
+        }
+
+        let mut explored = Vec::new();
+        let mut path = Vec::new();
+        if let Some(solver) = solver {
+            let endpoint = |n: &Node| *n == maze.start || *n == maze.goal;
+            explored.extend(
+                solver
+                    .came_from
+                    .keys()
+                    .filter(|n| !endpoint(n))
+                    .map(|&n| (tile(n), COLOR_PATH)),
+            );
+            if let Some(nodes) = &solver.path {
+                path.extend(
+                    nodes
+                        .iter()
+                        .filter(|n| !endpoint(n))
+                        .map(|&n| (tile(n), GREEN)),
+                );
+                for pair in nodes.windows(2).filter(|p| p[0].z != p[1].z) {
+                    let (a, b) = (point(pair[0].position()), point(pair[1].position()));
+                    path.push((Shape::Line(a, b, 2.0), GREEN));
+                }
+            }
+        }
+
+        let mut hazards = Vec::new();
+        for obstacle in obstacles {
+            let c = point(obstacle.position.position());
+            let h = cell_size / 2.0;
+            hazards.push((tile(obstacle.position), obstacle.color));
+            hazards.push((Shape::Line(c - vec2(h, h), c + vec2(h, h), 2.0), BLACK));
+            hazards.push((Shape::Line(c + vec2(h, -h), c + vec2(-h, h), 2.0), BLACK));
+        }
+
+        let mut movers = Vec::new();
+        for agent in agents {
+            let trail = Color::new(agent.color.r, agent.color.g, agent.color.b, 0.6);
+            for pair in agent.trail.windows(2) {
+                // Trails jump between floor panels on stairs
+                if pair[0].z.round() == pair[1].z.round() {
+                    movers.push((Shape::Line(point(pair[0]), point(pair[1]), 4.0), trail));
+                }
+            }
+            let c = point(agent.position);
+            movers.push((Shape::Circle(c, cell_size / 1.5), WHITE));
+            movers.push((Shape::Circle(c, cell_size / 1.8), agent.color));
+            if agent.is_main {
+                movers.push((Shape::Circle(c, cell_size / 3.0), YELLOW));
+            }
+        }
+
+        Self {
+            width: panel * maze.floors as f32,
+            height: maze.topology.pixel_height(maze.height, cell_size),
+            layers: vec![
+                Layer {
+                    name: "maze",
+                    shapes: walls,
+                },
+                Layer {
+                    name: "explored",
+                    shapes: explored,
+                },
+                Layer {
+                    name: "path",
+                    shapes: path,
+                },
+                Layer {
+                    name: "obstacles",
+                    shapes: hazards,
+                },
+                Layer {
+                    name: "agents",
+                    shapes: movers,
+                },
+            ],
+        }
+    }
+
+    pub fn save_svg(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w:.0}" height="{h:.0}" viewBox="0 0 {w:.1} {h:.1}">"#,
+            w = self.width,
+            h = self.height
+        );
+        let _ = writeln!(
+            svg,
+            r#"<rect width="100%" height="100%" fill="{}"/>"#,
+            svg_color(LIGHTGRAY)
+        );
+
+        for layer in &self.layers {
+            let _ = writeln!(svg, r#"<g id="{}">"#, layer.name);
+            for (shape, color) in &layer.shapes {
+                let fill = svg_paint("fill", *color);
+                let _ = match shape {
+                    // Opaque tiles get a hairline in their own color to hide seams between them
+                    Shape::Polygon(points) => {
+                        let points: Vec<String> = points
+                            .iter()
+                            .map(|p| format!("{:.2},{:.2}", p.x, p.y))
+                            .collect();
+                        let seam = if color.a >= 1.0 {
+                            format!(r#" {} stroke-width="0.5""#, svg_paint("stroke", *color))
+                        } else {
+                            String::new()
+                        };
+                        writeln!(
+                            svg,
+                            r#"<polygon points="{}" {fill}{seam}/>"#,
+                            points.join(" ")
+                        )
+                    }
+                    Shape::Circle(c, r) => writeln!(
+                        svg,
+                        r#"<circle cx="{:.2}" cy="{:.2}" r="{:.2}" {fill}/>"#,
+                        c.x, c.y, r
+                    ),
+                    Shape::Line(a, b, width) => writeln!(
+                        svg,
+                        r#"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}" {} stroke-width="{:.1}" stroke-linecap="round"/>"#,
+                        a.x,
+                        a.y,
+                        b.x,
+                        b.y,
+                        svg_paint("stroke", *color),
+                        width
+                    ),
+                };
+            }
+            svg.push_str("</g>\n");
+        }
+        svg.push_str("</svg>\n");
+        fs::write(path, svg)
+    }
+
+    // Rasterized on the CPU, so it works at any size regardless of the window
+    pub fn rasterize(&self) -> Canvas {
+        let (width, height) = (self.width.ceil() as usize, self.height.ceil() as usize);
+        let mut canvas = Canvas {
+            width,
+            height,
+            pixels: vec![LIGHTGRAY; width * height],
+        };
+
+        for layer in &self.layers {
+            for (shape, color) in &layer.shapes {
+                match shape {
+                    Shape::Polygon(points) => {
+                        canvas.fill(points, *color, |p| contains_point(points, p))
+                    }
+                    Shape::Circle(c, r) => {
+                        let bounds = [*c - Vec2::splat(*r), *c + Vec2::splat(*r)];
+                        canvas.fill(&bounds, *color, |p| p.distance(*c) <= *r)
+                    }
+                    Shape::Line(a, b, width) => {
+                        let h = Vec2::splat(width / 2.0);
+                        let bounds = [a.min(*b) - h, a.max(*b) + h];
+                        canvas.fill(&bounds, *color, |p| {
+                            segment_distance(p, *a, *b) <= width / 2.0
+                        })
+                    }
+                }
+            }
+        }
+        canvas
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.rasterize().save_png(path)
+    }
+}
+
+pub struct Canvas {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<Color>, // Row-major, always opaque
+}
+
+impl Canvas {
+    pub fn rgb(&self) -> Vec<[u8; 3]> {
+        self.pixels
+            .iter()
+            .map(|&c| {
+                let [r, g, b, _]: [u8; 4] = c.into();
+                [r, g, b]
+            })
+            .collect()
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let bytes: Vec<u8> = self.rgb().concat();
+        image::save_buffer(
+            path,
+            &bytes,
+            self.width as u32,
+            self.height as u32,
+            image::ColorType::Rgb8,
+        )
+        .map_err(|err| io::Error::other(err.to_string()))
+    }
+
+    // Blend `color` into every pixel whose center is inside the shape, scanning only
+    // the bounding box of `points`
+    fn fill(&mut self, points: &[Vec2], color: Color, inside: impl Fn(Vec2) -> bool) {
+        let (min, max) = points.iter().fold(
+            (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+            |(lo, hi), p| (lo.min(*p), hi.max(*p)),
+        );
+        let x0 = min.x.floor().max(0.0) as usize;
+        let y0 = min.y.floor().max(0.0) as usize;
+        let x1 = (max.x.ceil().max(0.0) as usize).min(self.width);
+        let y1 = (max.y.ceil().max(0.0) as usize).min(self.height);
+
+        for y in y0..y1 {
+            for x in x0..x1 {
+                if inside(vec2(x as f32 + 0.5, y as f32 + 0.5)) {
+                    let pixel = &mut self.pixels[y * self.width + x];
+                    let a = color.a;
+                    *pixel = Color::new(
+                        color.r * a + pixel.r * (1.0 - a),
+                        color.g * a + pixel.g * (1.0 - a),
+                        color.b * a + pixel.b * (1.0 - a),
+                        1.0,
+                    );
+                }
+            }
+        }
+    }
+}
+
+// Even-odd rule, so ring segments (which aren't convex) work too
+fn contains_point(polygon: &[Vec2], p: Vec2) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        let (a, b) = (polygon[i], polygon[j]);
+        if (a.y > p.y) != (b.y > p.y) && p.x < (b.x - a.x) * (p.y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+fn segment_distance(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let t = if ab.length_squared() > 0.0 {
+        ((p - a).dot(ab) / ab.length_squared()).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    p.distance(a + ab * t)
+}
+
+fn svg_color(c: Color) -> String {
+    let [r, g, b, _]: [u8; 4] = c.into();
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn svg_paint(attr: &str, c: Color) -> String {
+    if c.a >= 1.0 {
+        format!(r#"{attr}="{}""#, svg_color(c))
+    } else {
+        format!(r#"{attr}="{}" {attr}-opacity="{:.2}""#, svg_color(c), c.a)
+    }
+}
@@ -0,0 +1,402 @@
+// Arithmetic over a cell and its goal, for heuristics typed in at runtime:
+//   numbers, + - * / ^, parentheses, unary minus
+//   dx, dy, dz (goal minus cell), x, y, z, gx, gy, gz (cell and goal; z is the floor),
+//   w, h (grid size)
+//   abs(a), sqrt(a), max(a, b, ...), min(a, b, ...)
+
+pub const VARIABLES: &[&str] = &["dx", "dy", "dz", "x", "y", "z", "gx", "gy", "gz", "w", "h"];
+pub const FUNCTIONS: &[&str] = &["abs", "sqrt", "max", "min"];
+
+// What a formula is evaluated against
+pub struct Vars {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub gx: f32,
+    pub gy: f32,
+    pub gz: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Var {
+    Dx,
+    Dy,
+    Dz,
+    X,
+    Y,
+    Z,
+    Gx,
+    Gy,
+    Gz,
+    W,
+    H,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Func {
+    Abs,
+    Sqrt,
+    Max,
+    Min,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+#[derive(Clone, Debug)]
+pub enum Expr {
+    Number(f32),
+    Var(Var),
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    Call(Func, Vec<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f32),
+    Ident(String),
+    Symbol(char),
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+        };
+        if parser.tokens.is_empty() {
+            return Err("empty formula".to_string());
+        }
+        let expr = parser.sum()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expr),
+            Some(token) => Err(format!("unexpected {}", describe(token))),
+        }
+    }
+
+    pub fn eval(&self, vars: &Vars) -> f32 {
+        match self {
+            Expr::Number(n) => *n,
+            Expr::Var(var) => match var {
+                Var::Dx => vars.gx - vars.x,
+                Var::Dy => vars.gy - vars.y,
+                Var::Dz => vars.gz - vars.z,
+                Var::X => vars.x,
+                Var::Y => vars.y,
+                Var::Z => vars.z,
+                Var::Gx => vars.gx,
+                Var::Gy => vars.gy,
+                Var::Gz => vars.gz,
+                Var::W => vars.w,
+                Var::H => vars.h,
+            },
+            Expr::Neg(a) => -a.eval(vars),
+            Expr::Binary(op, a, b) => {
+                let (a, b) = (a.eval(vars), b.eval(vars));
+                match op {
+                    Op::Add => a + b,
+                    Op::Sub => a - b,
+                    Op::Mul => a * b,
+                    Op::Div => a / b,
+                    Op::Pow => a.powf(b),
+                }
+            }
+            Expr::Call(func, args) => {
+                let mut values = args.iter().map(|a| a.eval(vars));
+                match func {
+                    Func::Abs => values.next().unwrap_or(0.0).abs(),
+                    Func::Sqrt => values.next().unwrap_or(0.0).sqrt(),
+                    Func::Max => values.fold(f32::NEG_INFINITY, f32::max),
+                    Func::Min => values.fold(f32::INFINITY, f32::min),
+                }
+            }
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut text = String::new();
+            while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit() || **d == '.') {
+                text.push(d);
+                chars.next();
+            }
+            let n = text
+                .parse()
+                .map_err(|_| format!("'{}' is not a number", text))?;
+            tokens.push(Token::Number(n));
+        } else if c.is_ascii_alphabetic() {
+            let mut text = String::new();
+            while let Some(&d) = chars.peek().filter(|d| d.is_ascii_alphanumeric()) {
+                text.push(d.to_ascii_lowercase());
+                chars.next();
+            }
+            tokens.push(Token::Ident(text));
+        } else if "+-*/^(),".contains(c) {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else {
+            return Err(format!("unexpected '{}'", c));
+        }
+    }
+    Ok(tokens)
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Number(n) => format!("number {}", n),
+        Token::Ident(name) => format!("'{}'", name),
+        Token::Symbol(c) => format!("'{}'", c),
+    }
+}
+
+// Recursive descent, one method per precedence level
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek_symbol(&self) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Symbol(c)) => Some(*c),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), String> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Symbol(c)) if *c == symbol => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(token) => Err(format!("expected '{}', found {}", symbol, describe(token))),
+            None => Err(format!("expected '{}' at the end", symbol)),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut expr = self.product()?;
+        while let Some(c @ ('+' | '-')) = self.peek_symbol() {
+            self.pos += 1;
+            let op = if c == '+' { Op::Add } else { Op::Sub };
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.product()?));
+        }
+        Ok(expr)
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while let Some(c @ ('*' | '/')) = self.peek_symbol() {
+            self.pos += 1;
+            let op = if c == '*' { Op::Mul } else { Op::Div };
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.peek_symbol() == Some('-') {
+            self.pos += 1;
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    // Right-associative, and tighter than unary minus on its left: -x^2 is -(x^2)
+    fn power(&mut self) -> Result<Expr, String> {
+        let base = self.atom()?;
+        if self.peek_symbol() == Some('^') {
+            self.pos += 1;
+            return Ok(Expr::Binary(
+                Op::Pow,
+                Box::new(base),
+                Box::new(self.unary()?),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        let Some(token) = self.tokens.get(self.pos).cloned() else {
+            return Err("formula ends too early".to_string());
+        };
+        self.pos += 1;
+        match token {
+            Token::Number(n) => Ok(Expr::Number(n)),
+            Token::Symbol('(') => {
+                let expr = self.sum()?;
+                self.expect(')')?;
+                Ok(expr)
+            }
+            Token::Ident(name) if self.peek_symbol() == Some('(') => self.call(&name),
+            Token::Ident(name) => variable(&name).map(Expr::Var).ok_or_else(|| {
+                format!("unknown variable '{}' (use {})", name, VARIABLES.join(", "))
+            }),
+            token => Err(format!("unexpected {}", describe(&token))),
+        }
+    }
+
+    fn call(&mut self, name: &str) -> Result<Expr, String> {
+        let func = match name {
+            "abs" => Func::Abs,
+            "sqrt" => Func::Sqrt,
+            "max" => Func::Max,
+            "min" => Func::Min,
+            _ => {
+                return Err(format!(
+                    "unknown function '{}' (use {})",
+                    name,
+                    FUNCTIONS.join(", ")
+                ));
+            }
+        };
+        self.expect('(')?;
+        let mut args = vec![self.sum()?];
+        while self.peek_symbol() == Some(',') {
+            self.pos += 1;
+            args.push(self.sum()?);
+        }
+        self.expect(')')?;
+
+        let arity_ok = match func {
+            Func::Abs | Func::Sqrt => args.len() == 1,
+            Func::Max | Func::Min => args.len() >= 2,
+        };
+        if !arity_ok {
+            let wanted = match func {
+                Func::Abs | Func::Sqrt => "one argument",
+                Func::Max | Func::Min => "at least two arguments",
+            };
+            return Err(format!("{} takes {}", name, wanted));
+        }
+        Ok(Expr::Call(func, args))
+    }
+}
+
+fn variable(name: &str) -> Option<Var> {
+    Some(match name {
+        "dx" => Var::Dx,
+        "dy" => Var::Dy,
+        "dz" => Var::Dz,
+        "x" => Var::X,
+        "y" => Var::Y,
+        "z" => Var::Z,
+        "gx" => Var::Gx,
+        "gy" => Var::Gy,
+        "gz" => Var::Gz,
+        "w" => Var::W,
+        "h" => Var::H,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> Vars {
+        Vars {
+            x: 1.0,
+            y: 2.0,
+            z: 0.0,
+            gx: 4.0,
+            gy: 6.0,
+            gz: 2.0,
+            w: 21.0,
+            h: 11.0,
+        }
+    }
+
+    fn eval(source: &str) -> f32 {
+        Expr::parse(source).unwrap().eval(&vars())
+    }
+
+    fn error(source: &str) -> String {
+        Expr::parse(source).unwrap_err()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("2 + 3 * 4"), 14.0);
+        assert_eq!(eval("(2 + 3) * 4"), 20.0);
+        assert_eq!(eval("2 * 3 ^ 2"), 18.0);
+        assert_eq!(eval("-2 ^ 2"), -4.0);
+        assert_eq!(eval("(-2) ^ 2"), 4.0);
+        assert_eq!(eval("2 ^ -1"), 0.5);
+        assert_eq!(eval("-3 * -2"), 6.0);
+        assert!(matches!(
+            Expr::parse("1 + 2 * 3").unwrap(),
+            Expr::Binary(Op::Add, _, b) if matches!(*b, Expr::Binary(Op::Mul, _, _))
+        ));
+    }
+
+    #[test]
+    fn associativity() {
+        assert_eq!(eval("8 - 3 - 2"), 3.0);
+        assert_eq!(eval("16 / 4 / 2"), 2.0);
+        assert_eq!(eval("2 ^ 3 ^ 2"), 512.0);
+        assert!(matches!(
+            Expr::parse("2 ^ 3 ^ 2").unwrap(),
+            Expr::Binary(Op::Pow, a, _) if matches!(*a, Expr::Number(_))
+        ));
+    }
+
+    #[test]
+    fn variables_and_functions() {
+        assert_eq!(eval("dx + dy + dz"), 3.0 + 4.0 + 2.0);
+        assert_eq!(eval("x + y + z"), 3.0);
+        assert_eq!(eval("gx * gy * gz"), 48.0);
+        assert_eq!(eval("w - h"), 10.0);
+        assert_eq!(eval("ABS(x - gx)"), 3.0);
+        assert_eq!(eval("sqrt(dx^2 + dy^2)"), 5.0);
+        assert_eq!(eval("max(dx, dy, dz)"), 4.0);
+        assert_eq!(eval("min(dx, dy, dz)"), 2.0);
+        assert_eq!(eval(" 1.5*( abs(dx)+abs(dy) ) "), 10.5);
+    }
+
+    #[test]
+    fn eval_passes_nan_and_infinity_through() {
+        // Clamping is left to the heuristic; eval itself is plain float arithmetic
+        assert!(eval("sqrt(-1)").is_nan());
+        assert_eq!(eval("1 / 0"), f32::INFINITY);
+        assert_eq!(eval("-1 / 0"), f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn error_messages() {
+        assert_eq!(error(""), "empty formula");
+        assert_eq!(error("   "), "empty formula");
+        assert_eq!(error("1 +"), "formula ends too early");
+        assert_eq!(error("(1 + 2"), "expected ')' at the end");
+        assert_eq!(error("max(1 2)"), "expected ')', found number 2");
+        assert_eq!(error("1 2"), "unexpected number 2");
+        assert_eq!(error("1 + )"), "unexpected ')'");
+        assert_eq!(error("1 $ 2"), "unexpected '$'");
+        assert_eq!(error("1..2"), "'1..2' is not a number");
+        assert_eq!(
+            error("dq"),
+            "unknown variable 'dq' (use dx, dy, dz, x, y, z, gx, gy, gz, w, h)"
+        );
+        assert_eq!(
+            error("log(2)"),
+            "unknown function 'log' (use abs, sqrt, max, min)"
+        );
+        assert_eq!(error("abs(1, 2)"), "abs takes one argument");
+        assert_eq!(error("min(1)"), "min takes at least two arguments");
+    }
+}
@@ -1,26 +1,64 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::vec;
 
 use ::rand::Rng;
 use ::rand::prelude::IndexedRandom;
 use ::rand::seq::SliceRandom;
 
-use crate::constants::DENSITY;
+use crate::constants::{STAIR_CHANCE, WEAVE_DENSITY};
+use crate::helper::{find_set, union_sets};
 use crate::maze::{Maze, Tile};
 use crate::node::Node;
+use crate::topology::Topology;
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Algorithm {
     RecursiveBacktracker,
     Prims,
     Braid,
     Eller,
+    Kruskal,
+}
+
+impl Algorithm {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "backtracker" | "recursive-backtracker" => Some(Algorithm::RecursiveBacktracker),
+            "prims" | "prim" => Some(Algorithm::Prims),
+            "braid" => Some(Algorithm::Braid),
+            "eller" => Some(Algorithm::Eller),
+            "kruskal" => Some(Algorithm::Kruskal),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::RecursiveBacktracker => "backtracker",
+            Algorithm::Prims => "prims",
+            Algorithm::Braid => "braid",
+            Algorithm::Eller => "eller",
+            Algorithm::Kruskal => "kruskal",
+        }
+    }
+
+    // What actually runs on `maze`: Eller's row sweep needs straight rows on one floor,
+    // so on hex, polar or multi-floor mazes it falls back to the backtracker
+    pub fn for_maze(self, maze: &Maze) -> Self {
+        if self == Algorithm::Eller && (maze.topology != Topology::Square || maze.floors > 1) {
+            Algorithm::RecursiveBacktracker
+        } else {
+            self
+        }
+    }
 }
 
 #[derive(Clone, PartialEq)]
 pub enum EllerPhase {
     Initialize,
-    Horizontal { index: usize },
+    Horizontal {
+        index: usize,
+    },
     Vertical {
         verticals: Vec<usize>,
         current_idx: usize,
@@ -35,6 +73,11 @@ pub enum GeneratorState {
     Prims {
         frontier: Vec<Node>,
     },
+    Kruskal {
+        edges: Vec<(Node, Node, Node)>, // (cell, cell, wall), popped from the back
+        parent: Vec<usize>,
+        size: Vec<usize>,
+    },
     Eller {
         row: usize,
         sets: Vec<usize>,
@@ -53,40 +96,43 @@ pub enum GeneratorState {
 pub struct MazeVisualizer {
     pub state: GeneratorState,
     pub algorithm: Algorithm,
+    pub weave: bool,
+    pub density: f32, // Eller's join chance and the share of dead ends Braid opens
     pub done: bool,
 }
 
 impl MazeVisualizer {
-    pub fn new(maze: &mut Maze, algo: Algorithm) -> Self {
-        for y in 0..maze.height {
-            for x in 0..maze.width {
-                maze.grid[y][x] = Tile::Wall;
+    pub fn new(maze: &mut Maze, algo: Algorithm, weave: bool, density: f32) -> Self {
+        for layer in &mut maze.layers {
+            for row in layer.iter_mut() {
+                row.fill(Tile::Wall);
             }
         }
+        maze.stairs.clear();
+        maze.fit_endpoints_to_mask();
 
-        let start = maze.start;
+        let start = maze.lattice_cell(maze.start);
         let width = maze.width;
         let height = maze.height;
 
+        let algo = algo.for_maze(maze);
+
+        // Crossings are laid out on the square lattice only
+        let weave = weave && maze.topology == Topology::Square;
+        let mut rng = crate::rng::rng();
+
         let state = match algo {
             Algorithm::RecursiveBacktracker => {
-                maze.grid[start.y][start.x] = Tile::Path;
+                maze[start] = Tile::Path;
                 GeneratorState::RecursiveBacktracker { stack: vec![start] }
             }
             Algorithm::Prims | Algorithm::Braid => {
-                maze.grid[start.y][start.x] = Tile::Path;
-                let mut frontier = Vec::new();
-                let directions = [(0, -2), (2, 0), (0, 2), (-2, 0)];
-                for (dx, dy) in directions {
-                    let nx = start.x as isize + dx;
-                    let ny = start.y as isize + dy;
-                    if Maze::in_bounds(nx, ny, width, height) {
-                        frontier.push(Node {
-                            x: nx as usize,
-                            y: ny as usize,
-                        });
-                    }
-                }
+                maze[start] = Tile::Path;
+                let frontier = maze
+                    .cell_links(start)
+                    .into_iter()
+                    .map(|(cell, _)| cell)
+                    .collect();
                 GeneratorState::Prims { frontier }
             }
             Algorithm::Eller => {
@@ -101,46 +147,94 @@ impl MazeVisualizer {
                     phase: EllerPhase::Initialize,
                 }
             }
+            Algorithm::Kruskal => {
+                let cells: Vec<Node> = (0..maze.floors)
+                    .flat_map(|z| {
+                        (1..height - 1)
+                            .flat_map(move |y| (1..width - 1).map(move |x| Node::at(x, y, z)))
+                    })
+                    .filter(|&n| maze.topology.is_cell(n) && maze.contains(n))
+                    .collect();
+                for &cell in &cells {
+                    maze[cell] = Tile::Path;
+                }
+
+                let count = maze.floors * height * width;
+                let mut parent: Vec<usize> = (0..count).collect();
+                let mut size = vec![1; count];
+                if weave {
+                    place_crossings(maze, &cells, &mut parent, &mut size, &mut rng);
+                }
+
+                let mut edges: Vec<(Node, Node, Node)> = cells
+                    .iter()
+                    .flat_map(|&a| {
+                        maze.cell_links(a)
+                            .into_iter()
+                            .filter(move |(b, _)| (b.z, b.y, b.x) > (a.z, a.y, a.x))
+                            .map(move |(b, wall)| (a, b, wall))
+                    })
+                    .collect();
+                edges.shuffle(&mut rng);
+
+                // Most stairs wait until the floors need joining, so they stay rare
+                let (mut ordered, late): (Vec<_>, Vec<_>) = edges
+                    .into_iter()
+                    .partition(|(a, b, _)| a.z == b.z || rng.random_bool(STAIR_CHANCE as f64));
+                ordered.extend(late);
+                ordered.reverse();
+
+                GeneratorState::Kruskal {
+                    edges: ordered,
+                    parent,
+                    size,
+                }
+            }
         };
 
         MazeVisualizer {
             state,
             algorithm: algo,
+            weave,
+            density,
             done: false,
         }
     }
 
     pub fn step(&mut self, maze: &mut Maze) {
-        let mut rng = ::rand::rng();
+        let mut rng = crate::rng::rng();
         let width = maze.width;
         let height = maze.height;
 
         match &mut self.state {
             GeneratorState::RecursiveBacktracker { stack } => {
                 if let Some(&current) = stack.last() {
-                    let directions = &[(0, -2), (2, 0), (0, 2), (-2, 0)];
-                    let mut neighbors = Vec::new();
-
-                    for (dx, dy) in directions {
-                        let nx = current.x as isize + dx;
-                        let ny = current.y as isize + dy;
-
-                        if Maze::in_bounds(nx, ny, width, height) {
-                            let nx = nx as usize;
-                            let ny = ny as usize;
-                            if maze.grid[ny][nx] == Tile::Wall {
-                                neighbors.push((nx, ny, dx, dy));
-                            }
-                        }
-                    }
-
-                    if let Some(&(nx, ny, dx, dy)) = neighbors.choose(&mut rng) {
-                        let wall_x = (current.x as isize + dx / 2) as usize;
-                        let wall_y = (current.y as isize + dy / 2) as usize;
-
-                        maze.grid[wall_y][wall_x] = Tile::Path;
-                        maze.grid[ny][nx] = Tile::Path;
-                        stack.push(Node { x: nx, y: ny });
+                    let neighbors: Vec<(Node, Node)> = maze
+                        .cell_links(current)
+                        .into_iter()
+                        .filter(|(cell, _)| maze[*cell] == Tile::Wall)
+                        .collect();
+                    let neighbors = prefer_same_floor(current, neighbors, &mut rng);
+                    let tunnels = if self.weave {
+                        tunnel_links(maze, current)
+                    } else {
+                        Vec::new()
+                    };
+
+                    // Tunnels compete with ordinary moves on equal terms
+                    let pick = rng.random_range(0..(neighbors.len() + tunnels.len()).max(1));
+                    if let Some(&(cell, wall)) = neighbors.get(pick) {
+                        maze[cell] = Tile::Path;
+                        maze.open_link(current, cell, wall);
+                        stack.push(cell);
+                    } else if let Some(&[entry, bridge, exit, cell]) =
+                        tunnels.get(pick - neighbors.len())
+                    {
+                        maze[entry] = Tile::Tunnel;
+                        maze[bridge] = Tile::Bridge;
+                        maze[exit] = Tile::Tunnel;
+                        maze[cell] = Tile::Path;
+                        stack.push(cell);
                     } else {
                         stack.pop();
                     }
@@ -154,45 +248,35 @@ impl MazeVisualizer {
                     let rand_index = rng.random_range(0..frontier.len());
                     let current = frontier.swap_remove(rand_index);
 
-                    if maze.grid[current.y][current.x] == Tile::Path {
+                    if maze[current] != Tile::Wall {
                         return;
                     }
 
-                    maze.grid[current.y][current.x] = Tile::Path;
-
-                    let directions = [(0, -2), (2, 0), (0, 2), (-2, 0)];
-                    let mut neighbors = Vec::new();
-                    for (dx, dy) in directions {
-                        let nx = current.x as isize + dx;
-                        let ny = current.y as isize + dy;
-
-                        if Maze::in_bounds(nx, ny, width, height) {
-                            if maze.grid[ny as usize][nx as usize] == Tile::Path {
-                                neighbors.push(Node {
-                                    x: nx as usize,
-                                    y: ny as usize,
-                                });
-                            }
-                        }
+                    let links = maze.cell_links(current);
+                    let carved: Vec<(Node, Node)> = links
+                        .iter()
+                        .copied()
+                        .filter(|(cell, _)| maze[*cell] != Tile::Wall)
+                        .collect();
+
+                    // Cells only reachable by stairs wait their turn, so floors aren't riddled with them
+                    if carved.iter().all(|(cell, _)| cell.z != current.z)
+                        && !rng.random_bool(STAIR_CHANCE as f64)
+                    {
+                        frontier.push(current);
+                        return;
                     }
 
-                    if let Some(neighbor) = neighbors.choose(&mut rng) {
-                        let wall_x = (current.x + neighbor.x) / 2;
-                        let wall_y = (current.y + neighbor.y) / 2;
-                        maze.grid[wall_y][wall_x] = Tile::Path;
-                    }
+                    maze[current] = Tile::Path;
+                    let carved = prefer_same_floor(current, carved, &mut rng);
 
-                    for (dx, dy) in directions {
-                        let nx = current.x as isize + dx;
-                        let ny = current.y as isize + dy;
+                    if let Some(&(cell, wall)) = carved.choose(&mut rng) {
+                        maze.open_link(current, cell, wall);
+                    }
 
-                        if Maze::in_bounds(nx, ny, width, height) {
-                            if maze.grid[ny as usize][nx as usize] == Tile::Wall {
-                                frontier.push(Node {
-                                    x: nx as usize,
-                                    y: ny as usize,
-                                });
-                            }
+                    for (cell, _) in &links {
+                        if maze[*cell] == Tile::Wall {
+                            frontier.push(*cell);
                         }
                     }
                 } else {
@@ -204,6 +288,30 @@ impl MazeVisualizer {
                     }
                 }
             }
+            GeneratorState::Kruskal {
+                edges,
+                parent,
+                size,
+            } => {
+                // Merge one pair of regions per step
+                while let Some((a, b, wall)) = edges.pop() {
+                    // Walls already opened by a crossing are not up for grabs
+                    if a.z == b.z && maze[wall] != Tile::Wall {
+                        continue;
+                    }
+                    let (ia, ib) = (cell_index(maze, a), cell_index(maze, b));
+                    if find_set(parent, ia) != find_set(parent, ib) {
+                        maze.open_link(a, b, wall);
+                        union_sets(parent, size, ia, ib);
+                        break;
+                    }
+                }
+
+                if edges.is_empty() {
+                    self.done = true;
+                    self.state = GeneratorState::Finished;
+                }
+            }
             GeneratorState::Eller {
                 row,
                 sets,
@@ -218,8 +326,11 @@ impl MazeVisualizer {
 
                     match phase {
                         EllerPhase::Initialize => {
-                            for i in 0..num_cols {
-                                maze.grid[r][cols[i]] = Tile::Path;
+                            // Cells the mask leaves out stay wall and never join a set
+                            for &col in cols.iter() {
+                                if maze.inside(col as isize, r as isize) {
+                                    maze[Node::new(col, r)] = Tile::Path;
+                                }
                             }
                             *phase = EllerPhase::Horizontal { index: 0 };
                         }
@@ -227,19 +338,24 @@ impl MazeVisualizer {
                             if *index < num_cols - 1 {
                                 let i = *index;
                                 let col = cols[i];
-                                if sets[i] != sets[i + 1] {
+                                let open = |x: usize| maze.inside(x as isize, r as isize);
+                                if sets[i] != sets[i + 1]
+                                    && open(col)
+                                    && open(col + 1)
+                                    && open(col + 2)
+                                {
                                     let should_merge = if last_row {
                                         true
                                     } else {
-                                        rng.random_bool(DENSITY as f64)
+                                        rng.random_bool(self.density as f64)
                                     };
                                     if should_merge {
-                                        maze.grid[r][col + 1] = Tile::Path;
+                                        maze[Node::new(col + 1, r)] = Tile::Path;
                                         let old_set = sets[i + 1];
                                         let new_set = sets[i];
-                                        for j in 0..num_cols {
-                                            if sets[j] == old_set {
-                                                sets[j] = new_set;
+                                        for set in sets.iter_mut() {
+                                            if *set == old_set {
+                                                *set = new_set;
                                             }
                                         }
                                     }
@@ -247,6 +363,7 @@ impl MazeVisualizer {
                                 *index += 1;
                             } else {
                                 if last_row {
+                                    join_pieces(maze);
                                     if self.algorithm == Algorithm::Eller {
                                         self.start_adding_cycles(maze);
                                     } else {
@@ -254,12 +371,20 @@ impl MazeVisualizer {
                                         self.state = GeneratorState::Finished;
                                     }
                                 } else {
-                                    let mut set_to_cols: HashMap<usize, Vec<usize>> = HashMap::new();
-                                    for i in 0..num_cols {
-                                        set_to_cols.entry(sets[i]).or_default().push(i);
+                                    // Ordered, so the random draws below happen in a fixed order
+                                    let mut set_to_cols: BTreeMap<usize, Vec<usize>> =
+                                        BTreeMap::new();
+                                    // Only cells with an unmasked cell below can lead down
+                                    for (i, &set_id) in sets.iter().enumerate() {
+                                        if (r..=r + 2)
+                                            .all(|y| maze.inside(cols[i] as isize, y as isize))
+                                        {
+                                            set_to_cols.entry(set_id).or_default().push(i);
+                                        }
                                     }
 
-                                    let mut next_row_sets: Vec<usize> = Vec::with_capacity(num_cols);
+                                    let mut next_row_sets: Vec<usize> =
+                                        Vec::with_capacity(num_cols);
                                     for _ in 0..num_cols {
                                         next_row_sets.push(*next_set_id);
                                         *next_set_id += 1;
@@ -276,7 +401,7 @@ impl MazeVisualizer {
                                             let should_connect = if connected_count == 0 {
                                                 true
                                             } else {
-                                                rng.random_bool(DENSITY as f64)
+                                                rng.random_bool(self.density as f64)
                                             };
 
                                             if should_connect {
@@ -304,8 +429,8 @@ impl MazeVisualizer {
                             if *current_idx < verticals.len() {
                                 let col_idx = verticals[*current_idx];
                                 let col = cols[col_idx];
-                                maze.grid[r + 1][col] = Tile::Path;
-                                maze.grid[r + 2][col] = Tile::Path;
+                                maze[Node::new(col, r + 1)] = Tile::Path;
+                                maze[Node::new(col, r + 2)] = Tile::Path;
                                 *current_idx += 1;
                             } else {
                                 *sets = next_row_sets.clone();
@@ -315,6 +440,7 @@ impl MazeVisualizer {
                         }
                     }
                 } else {
+                    join_pieces(maze);
                     if self.algorithm == Algorithm::Eller {
                         self.start_adding_cycles(maze);
                     } else {
@@ -332,27 +458,19 @@ impl MazeVisualizer {
                     let node = dead_ends[*current_index];
                     *current_index += 1;
 
-                    let jump_dirs = [(0, -2), (0, 2), (-2, 0), (2, 0)];
-                    let mut potential_walls = Vec::new();
-
-                    for (dx, dy) in jump_dirs {
-                        let nx = node.x as isize + dx;
-                        let ny = node.y as isize + dy;
-
-                        if Maze::in_bounds(nx, ny, width, height) {
-                            if maze.grid[ny as usize][nx as usize] == Tile::Path {
-                                potential_walls.push((dx, dy));
-                            }
-                        }
-                    }
-
-                    if let Some((dx, dy)) = potential_walls.choose(&mut rng) {
-                        let wall_x = node.x as isize + dx / 2;
-                        let wall_y = node.y as isize + dy / 2;
-
-                        if Maze::in_bounds(wall_x, wall_y, width, height) {
-                            maze.grid[wall_y as usize][wall_x as usize] = Tile::Path;
-                        }
+                    // Only same-floor walls, so cycles don't litter the maze with stairs
+                    let potential_walls: Vec<(Node, Node)> = maze
+                        .cell_links(node)
+                        .into_iter()
+                        .filter(|(cell, wall)| {
+                            cell.z == node.z
+                                && maze[*cell] != Tile::Wall
+                                && maze[*wall] == Tile::Wall
+                        })
+                        .collect();
+
+                    if let Some(&(cell, wall)) = potential_walls.choose(&mut rng) {
+                        maze.open_link(node, cell, wall);
                     }
                 } else {
                     self.done = true;
@@ -365,40 +483,42 @@ impl MazeVisualizer {
         }
 
         if self.done {
-            maze.grid[maze.start.y][maze.start.x] = Tile::Start;
-            maze.grid[maze.goal.y][maze.goal.x] = Tile::Goal;
+            maze.connect_to_lattice(maze.start);
+            let start = maze.start;
+            maze[start] = Tile::Start;
+            for goal in maze.goals() {
+                maze.connect_to_lattice(goal);
+                maze[goal] = Tile::Goal;
+            }
         }
     }
 
     fn start_adding_cycles(&mut self, maze: &Maze) {
         let mut dead_ends: Vec<Node> = Vec::new();
-        let directions = [(0, 1), (0, -1), (1, 0), (-1, 0)];
         let width = maze.width;
         let height = maze.height;
 
-        for y in 1..height - 1 {
-            for x in 1..width - 1 {
-                if maze.grid[y][x] == Tile::Path {
-                    let mut wall_count = 0;
-                    for (dx, dy) in &directions {
-                        let nx = x as isize + dx;
-                        let ny = y as isize + dy;
-                        if Maze::in_bounds(nx, ny, width, height) {
-                            if maze.grid[ny as usize][nx as usize] == Tile::Wall {
-                                wall_count += 1;
-                            }
+        for z in 0..maze.floors {
+            for y in 1..height - 1 {
+                for x in 1..width - 1 {
+                    let node = Node::at(x, y, z);
+                    if maze.topology.is_cell(node) && maze[node] != Tile::Wall {
+                        let exits = maze
+                            .neighbors(node)
+                            .iter()
+                            .filter(|n| maze[**n] != Tile::Wall)
+                            .count();
+                        if exits <= 1 {
+                            dead_ends.push(node);
                         }
                     }
-                    if wall_count >= 3 {
-                        dead_ends.push(Node { x, y });
-                    }
                 }
             }
         }
 
-        let mut rng = ::rand::rng();
+        let mut rng = crate::rng::rng();
         dead_ends.shuffle(&mut rng);
-        let target_count = (dead_ends.len() as f32 * DENSITY) as usize;
+        let target_count = (dead_ends.len() as f32 * self.density) as usize;
 
         self.state = GeneratorState::AddingCycles {
             dead_ends,
@@ -407,3 +527,227 @@ impl MazeVisualizer {
         };
     }
 }
+
+// Stairs are only taken occasionally, or when no link on the same floor is left
+fn prefer_same_floor(
+    current: Node,
+    links: Vec<(Node, Node)>,
+    rng: &mut impl Rng,
+) -> Vec<(Node, Node)> {
+    let same_floor: Vec<(Node, Node)> = links
+        .iter()
+        .copied()
+        .filter(|(cell, _)| cell.z == current.z)
+        .collect();
+
+    if same_floor.is_empty() || rng.random_bool(STAIR_CHANCE as f64) {
+        links
+    } else {
+        same_floor
+    }
+}
+
+// A mask can split Eller's rows so that some sets never meet again. Open one wall
+// between every two touching pieces, so each region of the mask is a single maze
+fn join_pieces(maze: &mut Maze) {
+    if maze.mask.is_none() {
+        return;
+    }
+    let count = maze.floors * maze.height * maze.width;
+    let mut parent: Vec<usize> = (0..count).collect();
+    let mut size = vec![1; count];
+    let cells: Vec<Node> = (1..maze.height - 1)
+        .flat_map(|y| (1..maze.width - 1).map(move |x| Node::new(x, y)))
+        .filter(|&n| maze.topology.is_cell(n) && maze[n] != Tile::Wall)
+        .collect();
+
+    let mut walls = Vec::new();
+    for &cell in &cells {
+        for (next, wall) in maze.cell_links(cell) {
+            if next.z != cell.z || maze[next] == Tile::Wall {
+                continue;
+            }
+            if maze[wall] == Tile::Wall {
+                walls.push((cell, next, wall));
+            } else {
+                let (a, b) = (cell_index(maze, cell), cell_index(maze, next));
+                if find_set(&mut parent, a) != find_set(&mut parent, b) {
+                    union_sets(&mut parent, &mut size, a, b);
+                }
+            }
+        }
+    }
+
+    walls.shuffle(&mut crate::rng::rng());
+    for (a, b, wall) in walls {
+        let (ia, ib) = (cell_index(maze, a), cell_index(maze, b));
+        if find_set(&mut parent, ia) != find_set(&mut parent, ib) {
+            maze.open_link(a, b, wall);
+            union_sets(&mut parent, &mut size, ia, ib);
+        }
+    }
+}
+
+fn cell_index(maze: &Maze, node: Node) -> usize {
+    (node.z * maze.height + node.y) * maze.width + node.x
+}
+
+fn is_endpoint_cell(maze: &Maze, cell: Node) -> bool {
+    maze.goals()
+        .into_iter()
+        .chain([maze.start])
+        .any(|n| n == cell || maze.lattice_cell(n) == cell)
+}
+
+// Ways to dig under a straight perpendicular corridor: [entry, bridge, exit, cell beyond]
+fn tunnel_links(maze: &Maze, current: Node) -> Vec<[Node; 4]> {
+    let count = maze.topology.direction_count();
+    (0..count)
+        .filter_map(|dir| {
+            let entry = maze.neighbor(current, dir)?;
+            let bridge = maze.neighbor(entry, dir)?;
+            let exit = maze.neighbor(bridge, dir)?;
+            let cell = maze.neighbor(exit, dir)?;
+
+            let side = |d: usize| maze.neighbor(bridge, d).map(|n| maze[n]);
+            let crosswise = side((dir + 1) % count) == Some(Tile::Path)
+                && side((dir + count - 1) % count) == Some(Tile::Path);
+
+            (maze[entry] == Tile::Wall
+                && maze[bridge] == Tile::Path
+                && maze[exit] == Tile::Wall
+                && maze[cell] == Tile::Wall
+                && crosswise
+                && !is_endpoint_cell(maze, bridge))
+            .then_some([entry, bridge, exit, cell])
+        })
+        .collect()
+}
+
+// Kruskal weave: lay down crossings before any other passage is carved
+fn place_crossings(
+    maze: &mut Maze,
+    cells: &[Node],
+    parent: &mut [usize],
+    size: &mut [usize],
+    rng: &mut impl Rng,
+) {
+    let count = maze.topology.direction_count();
+    let mut candidates = cells.to_vec();
+    candidates.shuffle(rng);
+    let attempts = (candidates.len() as f32 * WEAVE_DENSITY) as usize;
+
+    for &center in candidates.iter().take(attempts) {
+        if is_endpoint_cell(maze, center) {
+            continue;
+        }
+        let links: Vec<(Node, Node)> = (0..count)
+            .filter_map(|dir| {
+                let wall = maze.neighbor(center, dir)?;
+                let cell = maze.neighbor(wall, dir)?;
+                Some((cell, wall))
+            })
+            .collect();
+        if links.len() != count || links.iter().any(|&(_, wall)| maze[wall] != Tile::Wall) {
+            continue;
+        }
+
+        // All five cells must still be separate regions, otherwise we'd close a loop
+        let mut roots: Vec<usize> = links
+            .iter()
+            .map(|&(cell, _)| cell)
+            .chain([center])
+            .map(|n| find_set(parent, cell_index(maze, n)))
+            .collect();
+        roots.sort_unstable();
+        roots.dedup();
+        if roots.len() != count + 1 {
+            continue;
+        }
+
+        let over = rng.random_range(0..count / 2);
+        let c = cell_index(maze, center);
+        maze[center] = Tile::Bridge;
+        for (dir, &(cell, wall)) in links.iter().enumerate() {
+            let i = cell_index(maze, cell);
+            if dir % 2 == over {
+                maze[wall] = Tile::Path;
+                union_sets(parent, size, c, i);
+            } else {
+                maze[wall] = Tile::Tunnel;
+            }
+        }
+        // The tunnel joins the two cells on either side of the bridge
+        let (a, b) = (links[(over + 1) % 2].0, links[(over + 1) % 2 + 2].0);
+        union_sets(parent, size, cell_index(maze, a), cell_index(maze, b));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mask::Mask;
+
+    // An arch: the top rows span the whole width, the legs below are split, so
+    // sets over the gap have no way down and must meet along the top
+    fn arch() -> Maze {
+        let mut maze = Maze::new(21, 21);
+        let art = "#######\n#######\n##...##\n##...##\n##...##\n##...##\n##...##\n";
+        maze.mask = Some(Mask::from_ascii(art).unwrap());
+        maze
+    }
+
+    fn generate(maze: &mut Maze, algorithm: Algorithm) -> MazeVisualizer {
+        let mut generator = MazeVisualizer::new(maze, algorithm, false, 0.5);
+        while !generator.done {
+            generator.step(maze);
+        }
+        generator
+    }
+
+    #[test]
+    fn masked_eller_carves_one_connected_maze() {
+        for _ in 0..20 {
+            let mut maze = arch();
+            let generator = generate(&mut maze, Algorithm::Eller);
+            assert_eq!(generator.algorithm, Algorithm::Eller);
+
+            let cells: Vec<Node> = (1..20)
+                .flat_map(|y| (1..20).map(move |x| Node::new(x, y)))
+                .filter(|&n| maze.topology.is_cell(n) && maze.inside(n.x as isize, n.y as isize))
+                .collect();
+            assert!(cells.iter().all(|&n| maze[n] != Tile::Wall));
+
+            let mut seen = vec![cells[0]];
+            let mut i = 0;
+            while i < seen.len() {
+                for (next, wall) in maze.cell_links(seen[i]) {
+                    if maze[wall] != Tile::Wall && !seen.contains(&next) {
+                        seen.push(next);
+                    }
+                }
+                i += 1;
+            }
+            assert!(cells.iter().all(|n| seen.contains(n)));
+
+            for y in 0..21 {
+                for x in 0..21 {
+                    if !maze.inside(x, y) {
+                        assert_eq!(maze[Node::new(x as usize, y as usize)], Tile::Wall);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn eller_falls_back_only_where_rows_do_not_line_up() {
+        assert_eq!(Algorithm::Eller.for_maze(&arch()), Algorithm::Eller);
+        let mut maze = Maze::new(21, 21);
+        maze.topology = Topology::Hex;
+        assert_eq!(
+            Algorithm::Eller.for_maze(&maze),
+            Algorithm::RecursiveBacktracker
+        );
+    }
+}
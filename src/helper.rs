@@ -2,6 +2,7 @@
 #![allow(unused_imports)]
 #![allow(unused_variables)]
 
+use macroquad::prelude::Color;
 use std::f32::consts::PI;
 
 use crate::constants::*;
@@ -13,19 +14,27 @@ pub fn direction(a: Node, goal: Node, width: usize, height: usize) -> f32 {
 
     let angle = dy.atan2(dx);
 
-    let angle = (angle + 2.0 * PI) % (2.0 * PI);
+    (angle + 2.0 * PI) % (2.0 * PI)
+}
 
-    angle
+// Blue (0) through green to red (1)
+pub fn gradient(t: f32) -> Color {
+    let t = t.clamp(0.0, 1.0);
+    if t < 0.5 {
+        Color::new(0.0, t * 2.0, 1.0 - t * 2.0, 1.0)
+    } else {
+        Color::new(t * 2.0 - 1.0, 2.0 - t * 2.0, 0.0, 1.0)
+    }
 }
 
-pub fn find_set(parent: &mut Vec<usize>, i: usize) -> usize {
+pub fn find_set(parent: &mut [usize], i: usize) -> usize {
     if parent[i] != i {
         parent[i] = find_set(parent, parent[i]);
     }
     parent[i]
 }
 
-pub fn union_sets(parent: &mut Vec<usize>, size: &mut Vec<usize>, x: usize, y: usize) {
+pub fn union_sets(parent: &mut [usize], size: &mut [usize], x: usize, y: usize) {
     let mut xroot = find_set(parent, x);
     let mut yroot = find_set(parent, y);
     if xroot != yroot {
@@ -35,4 +44,4 @@ pub fn union_sets(parent: &mut Vec<usize>, size: &mut Vec<usize>, x: usize, y: u
         parent[yroot] = xroot;
         size[xroot] += size[yroot];
     }
-}
\ No newline at end of file
+}
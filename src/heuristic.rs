@@ -2,18 +2,194 @@
 #![allow(unused_imports)]
 #![allow(unused_variables)]
 
-use crate::{constants::{MAZE_HEIGHT, MAZE_WIDTH}, helper::direction, node::Node};
+use crate::{
+    config::AppConfig,
+    constants::HEURISTIC_KEYS,
+    expr::{Expr, Vars},
+    helper::direction,
+    landmarks::Landmarks,
+    maze::{Connectivity, Maze},
+    node::Node,
+    topology::Topology,
+};
 
+use macroquad::prelude::KeyCode;
 use std::f32::consts::PI;
 
+pub type HeuristicFn = fn(Node, Node) -> f32;
+
+// An estimate of the cost from one cell to another. `prepare` runs once per maze, after
+// generation or an edit and before the first search with this heuristic, so it can keep
+// whatever it learns about the maze (its size, the start, distance tables) for `estimate`
+pub trait Heuristic {
+    fn name(&self) -> &str;
+
+    fn estimate(&self, a: Node, b: Node) -> f32;
+
+    fn prepare(&mut self, _maze: &Maze, _config: &AppConfig) {}
+
+    // Whether `estimate` never overestimates on this maze's grid and connectivity. Only
+    // then does a search weighted by w promise a cost within w times the optimum
+    fn admissible(&self, _maze: &Maze) -> bool {
+        false
+    }
+
+    // Anything worth showing on the maze while this heuristic is selected
+    fn draw(&self, _maze: &Maze, _cell_size: f32) {}
+}
+
+// A heuristic that only needs the two cells
+pub struct Plain {
+    pub name: &'static str,
+    pub f: HeuristicFn,
+    pub admissible_on: fn(&Maze) -> bool,
+}
+
+impl Plain {
+    pub const fn new(name: &'static str, f: HeuristicFn) -> Self {
+        Self {
+            name,
+            f,
+            admissible_on: never,
+        }
+    }
+
+    pub const fn admissible_on(mut self, grids: fn(&Maze) -> bool) -> Self {
+        self.admissible_on = grids;
+        self
+    }
+}
+
+impl Heuristic for Plain {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn estimate(&self, a: Node, b: Node) -> f32 {
+        (self.f)(a, b)
+    }
+
+    fn admissible(&self, maze: &Maze) -> bool {
+        (self.admissible_on)(maze)
+    }
+}
+
+// Grids each plain heuristic stays below the true cost on. A hex or polar step can
+// change both coordinates, and an 8-way diagonal costs √2 for two units of Manhattan
+fn never(_maze: &Maze) -> bool {
+    false
+}
+
+fn always(_maze: &Maze) -> bool {
+    true
+}
+
+fn square(maze: &Maze) -> bool {
+    maze.topology == Topology::Square
+}
+
+fn four_way_square(maze: &Maze) -> bool {
+    square(maze) && maze.connectivity == Connectivity::Four
+}
+
+// A 4-way square step moves at most one hex step too
+fn hex_or_four_way(maze: &Maze) -> bool {
+    maze.topology == Topology::Hex || four_way_square(maze)
+}
+
+// What plain Dijkstra searches with
+pub const ZERO: Plain = Plain::new("Uniform Cost Search", uniform_cost).admissible_on(always);
+
+// Every heuristic the app can pick, in key order: the first ten get 1-9 and 0. Entries
+// are prepared on first use after the maze changes, since ALT's tables are costly
+pub struct Heuristics {
+    entries: Vec<Box<dyn Heuristic>>,
+    stale: Vec<bool>, // Not prepared for the current maze yet
+}
+
+impl Heuristics {
+    pub fn builtin() -> Self {
+        let mut heuristics = Self {
+            entries: Vec::new(),
+            stale: Vec::new(),
+        };
+        heuristics.register(Plain::new("Manhattan", manhattan).admissible_on(four_way_square));
+        heuristics.register(Plain::new("Euclidean", euclidean).admissible_on(square));
+        heuristics.register(ZERO);
+        heuristics.register(Plain::new("Euclidean Squared", euclidean_squared));
+        heuristics.register(Plain::new("Weighted Manhattan", weighted_manhattan));
+        heuristics.register(Tiebreaker::default());
+        heuristics
+            .register(Plain::new("Hex Distance", hex_distance).admissible_on(hex_or_four_way));
+        heuristics.register(Plain::new("Radial Distance", radial_distance).admissible_on(always));
+        heuristics.register(Alt::default());
+        heuristics
+    }
+
+    // Adds a heuristic after the others and returns its index
+    pub fn register(&mut self, heuristic: impl Heuristic + 'static) -> usize {
+        self.entries.push(Box::new(heuristic));
+        self.stale.push(true);
+        self.entries.len() - 1
+    }
+
+    // Swaps the heuristic at `index` for another, e.g. a formula typed again
+    pub fn replace(&mut self, index: usize, heuristic: impl Heuristic + 'static) {
+        self.entries[index] = Box::new(heuristic);
+        self.stale[index] = true;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    // Out-of-range indices (e.g. from an old scenario file) fall back to the first entry
+    pub fn get(&self, index: usize) -> &dyn Heuristic {
+        self.entries.get(index).unwrap_or(&self.entries[0]).as_ref()
+    }
+
+    pub fn key(&self, index: usize) -> Option<KeyCode> {
+        HEURISTIC_KEYS.get(index).copied()
+    }
+
+    // Index and key of every entry that has one
+    pub fn keys(&self) -> impl Iterator<Item = (usize, KeyCode)> + '_ {
+        (0..self.len()).filter_map(|i| Some((i, self.key(i)?)))
+    }
+
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|h| h.name().eq_ignore_ascii_case(name))
+    }
+
+    // The maze or the settings heuristics read (connectivity, landmarks) changed
+    pub fn invalidate(&mut self) {
+        self.stale.fill(true);
+    }
+
+    // Readies one entry for `maze`, unless it already is
+    pub fn prepare(&mut self, index: usize, maze: &Maze, config: &AppConfig) {
+        if let Some(heuristic) = self.entries.get_mut(index)
+            && self.stale[index]
+        {
+            heuristic.prepare(maze, config);
+            self.stale[index] = false;
+        }
+    }
+}
+
+// Every heuristic also counts floors: each flight of stairs costs one step
+
 pub fn manhattan(a: Node, b: Node) -> f32 {
-    (a.x.abs_diff(b.x) + a.y.abs_diff(b.y)) as f32
+    (a.x.abs_diff(b.x) + a.y.abs_diff(b.y) + a.z.abs_diff(b.z)) as f32
 }
 
 pub fn euclidean(a: Node, b: Node) -> f32 {
     let dx = a.x as isize - b.x as isize;
     let dy = a.y as isize - b.y as isize;
-    ((dx * dx + dy * dy) as f32).sqrt()
+    let dz = a.z as isize - b.z as isize;
+    ((dx * dx + dy * dy + dz * dz) as f32).sqrt()
 }
 
 pub fn uniform_cost(_a: Node, _b: Node) -> f32 {
@@ -22,22 +198,152 @@ pub fn uniform_cost(_a: Node, _b: Node) -> f32 {
 
 pub fn euclidean_squared(a: Node, b: Node) -> f32 {
     let dx = a.x as isize - b.x as isize;
-    let dy = a.y as isize - b.y as isize;   
-    (dx * dx + dy * dy) as f32
+    let dy = a.y as isize - b.y as isize;
+    let dz = a.z as isize - b.z as isize;
+    (dx * dx + dy * dy + dz * dz) as f32
 }
 
 pub fn weighted_manhattan(a: Node, b: Node) -> f32 {
-    let h = (a.x.abs_diff(b.x) + a.y.abs_diff(b.y)) as f32;
-    h * 2.5
+    manhattan(a, b) * 2.5
+}
+
+// Manhattan, nudged by how far the direction to the goal is from the ideal sector
+#[derive(Default)]
+pub struct Tiebreaker {
+    width: usize,
+    height: usize,
+}
+
+impl Heuristic for Tiebreaker {
+    fn name(&self) -> &str {
+        "Manhattan with Tiebreaker"
+    }
+
+    fn prepare(&mut self, maze: &Maze, _config: &AppConfig) {
+        (self.width, self.height) = (maze.width, maze.height);
+    }
+
+    fn estimate(&self, a: Node, b: Node) -> f32 {
+        let h = manhattan(a, b);
+        let dir = direction(a, b, self.width, self.height);
+
+        let ideal_sector = (1.0_f32).atan2(2.0);
+
+        let tiebreaker = (dir - ideal_sector).abs() / (2.0 * PI);
+
+        h + h * tiebreaker * 0.5
+    }
+}
+
+// Step count on an odd-r hex grid (cube distance)
+pub fn hex_distance(a: Node, b: Node) -> f32 {
+    let (aq, ar) = Topology::hex_axial(a);
+    let (bq, br) = Topology::hex_axial(b);
+    let (dq, dr) = (aq - bq, ar - br);
+    ((dq.abs() + dr.abs() + (dq + dr).abs()) / 2) as f32 + a.z.abs_diff(b.z) as f32
+}
+
+// Rings apart on a polar grid; moving around a ring is free, so it never overestimates
+pub fn radial_distance(a: Node, b: Node) -> f32 {
+    (a.y.abs_diff(b.y) + a.z.abs_diff(b.z)) as f32
+}
+
+// Largest landmark gap (see landmarks.rs); exact along the corridors a landmark sits behind
+#[derive(Default)]
+pub struct Alt {
+    landmarks: Landmarks,
+}
+
+impl Heuristic for Alt {
+    fn name(&self) -> &str {
+        "ALT (Landmarks)"
+    }
+
+    fn prepare(&mut self, maze: &Maze, config: &AppConfig) {
+        self.landmarks = Landmarks::place(maze, config.landmarks, config.landmark_selection);
+    }
+
+    fn estimate(&self, a: Node, b: Node) -> f32 {
+        self.landmarks.estimate(a, b)
+    }
+
+    // Differences of true distances never exceed the distance between the two cells
+    fn admissible(&self, _maze: &Maze) -> bool {
+        true
+    }
+
+    fn draw(&self, maze: &Maze, cell_size: f32) {
+        self.landmarks.draw(maze, cell_size);
+    }
 }
 
-pub fn manhattan_tiebreaker(a: Node, b: Node) -> f32 {
-    let h = (a.x.abs_diff(b.x) + a.y.abs_diff(b.y)) as f32;
-    let dir = direction(a, b, MAZE_WIDTH, MAZE_HEIGHT);
-    
-    let ideal_sector = (1.0_f32).atan2(2.0);
+// A formula typed into the app (see expr.rs), evaluated per cell against the goal
+pub struct Formula {
+    name: String,
+    expr: Expr,
+    width: usize,
+    height: usize,
+}
+
+impl Formula {
+    pub fn new(source: &str) -> Result<Self, String> {
+        Ok(Self {
+            name: format!("h = {}", source),
+            expr: Expr::parse(source)?,
+            width: 0,
+            height: 0,
+        })
+    }
+}
 
-    let tiebreaker = (dir - ideal_sector).abs() / (2.0 * PI);
+impl Heuristic for Formula {
+    fn name(&self) -> &str {
+        &self.name
+    }
 
-    h + h * tiebreaker * 0.5
-}
\ No newline at end of file
+    fn prepare(&mut self, maze: &Maze, _config: &AppConfig) {
+        (self.width, self.height) = (maze.width, maze.height);
+    }
+
+    fn estimate(&self, a: Node, b: Node) -> f32 {
+        let h = self.expr.eval(&Vars {
+            x: a.x as f32,
+            y: a.y as f32,
+            z: a.z as f32,
+            gx: b.x as f32,
+            gy: b.y as f32,
+            gz: b.z as f32,
+            w: self.width as f32,
+            h: self.height as f32,
+        });
+        // NaN (say, sqrt of a negative), negative or infinite estimates would scramble
+        // the open list, so they become 0 or the largest finite cost
+        if h.is_nan() {
+            0.0
+        } else {
+            h.clamp(0.0, f32::MAX)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn estimate(source: &str) -> f32 {
+        Formula::new(source)
+            .unwrap()
+            .estimate(Node::at(1, 1, 0), Node::at(4, 5, 0))
+    }
+
+    #[test]
+    fn formula_estimates_are_clamped() {
+        assert_eq!(estimate("abs(dx) + abs(dy)"), 7.0);
+        assert_eq!(estimate("sqrt(-1)"), 0.0);
+        assert_eq!(estimate("-dx"), 0.0);
+        assert_eq!(estimate("1 / 0"), f32::MAX);
+        assert_eq!(estimate("-1 / 0"), 0.0);
+        assert_eq!(estimate("10 ^ 100"), f32::MAX);
+        assert_eq!(estimate("0 * (1 / 0)"), 0.0);
+    }
+}
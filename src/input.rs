@@ -0,0 +1,298 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::constants::HEURISTIC_KEYS;
+use crate::maze::Maze;
+use crate::node::Node;
+use crate::view::View;
+
+// Every key the app reacts to; only these are captured and logged
+const KEYS: &[KeyCode] = &[
+    KeyCode::Space,
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Enter,
+    KeyCode::Escape,
+    KeyCode::Backspace,
+    KeyCode::Home,
+    KeyCode::Minus,
+    KeyCode::Equal,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::LeftBracket,
+    KeyCode::RightBracket,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+];
+
+// Held modifiers that change what a key or click does
+const MODIFIERS: &[KeyCode] = &[
+    KeyCode::LeftShift,
+    KeyCode::RightShift,
+    KeyCode::LeftControl,
+    KeyCode::RightControl,
+];
+
+const BUTTONS: &[MouseButton] = &[MouseButton::Left, MouseButton::Right, MouseButton::Middle];
+
+// Everything the main loop reads from the user in one frame. The cursor is kept as the
+// tile under it, so replays don't depend on pan and zoom; the raw pointer is only
+// needed for on-screen widgets and panning
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(into = "FrameRecord", try_from = "FrameRecord")]
+pub struct FrameInput {
+    pub dt: f32,
+    pub pressed: Vec<KeyCode>,
+    pub held: Vec<KeyCode>,
+    pub clicked: Vec<MouseButton>,
+    pub buttons_down: Vec<MouseButton>,
+    pub cursor: Option<Node>,
+    pub pointer: Vec2,
+    pub wheel: f32,
+    pub text: String,         // Characters typed this frame
+    pub screen: Option<Vec2>, // New window size, on frames where it changed
+}
+
+impl FrameInput {
+    // `area` is the part of the screen showing the maze through `view`
+    pub fn capture(maze: &Maze, cell_size: f32, view: &View, area: Rect) -> Self {
+        let pointer: Vec2 = mouse_position().into();
+        Self {
+            dt: get_frame_time(),
+            pressed: KEYS
+                .iter()
+                .copied()
+                .chain(HEURISTIC_KEYS)
+                .filter(|&k| is_key_pressed(k))
+                .collect(),
+            held: MODIFIERS
+                .iter()
+                .copied()
+                .filter(|&k| is_key_down(k))
+                .collect(),
+            clicked: BUTTONS
+                .iter()
+                .copied()
+                .filter(|&b| is_mouse_button_pressed(b))
+                .collect(),
+            buttons_down: BUTTONS
+                .iter()
+                .copied()
+                .filter(|&b| is_mouse_button_down(b))
+                .collect(),
+            cursor: area
+                .contains(pointer)
+                .then(|| maze.tile_at(view.to_world(pointer), cell_size))
+                .flatten(),
+            pointer,
+            wheel: mouse_wheel().1,
+            text: std::iter::from_fn(get_char_pressed)
+                .filter(|c| !c.is_control())
+                .collect(),
+            screen: None,
+        }
+    }
+
+    pub fn key_pressed(&self, key: KeyCode) -> bool {
+        self.pressed.contains(&key)
+    }
+
+    pub fn shift(&self) -> bool {
+        self.held.contains(&KeyCode::LeftShift) || self.held.contains(&KeyCode::RightShift)
+    }
+
+    pub fn ctrl(&self) -> bool {
+        self.held.contains(&KeyCode::LeftControl) || self.held.contains(&KeyCode::RightControl)
+    }
+
+    pub fn button_pressed(&self, button: MouseButton) -> bool {
+        self.clicked.contains(&button)
+    }
+
+    pub fn button_down(&self, button: MouseButton) -> bool {
+        self.buttons_down.contains(&button)
+    }
+
+    // The same frame with the keyboard already taken, e.g. by a text prompt
+    pub fn without_keys(mut self) -> Self {
+        self.pressed.clear();
+        self.text.clear();
+        self
+    }
+}
+
+// A frame as it is logged: keys and buttons by name, and empty lists, a cursor off
+// the maze and the pointer while no button is involved left out
+#[derive(Serialize, Deserialize)]
+struct FrameRecord {
+    dt: f32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    keys: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    held: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    clicks: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    buttons: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cursor: Option<[usize; 3]>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    wheel: f32,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    screen: Option<[f32; 2]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pointer: Option<[f32; 2]>,
+}
+
+fn is_zero(v: &f32) -> bool {
+    *v == 0.0
+}
+
+impl From<FrameInput> for FrameRecord {
+    fn from(input: FrameInput) -> Self {
+        let names = |list: &[KeyCode]| list.iter().map(|k| format!("{:?}", k)).collect();
+        let buttons = |list: &[MouseButton]| list.iter().map(|b| format!("{:?}", b)).collect();
+        let pointer_used =
+            !input.buttons_down.is_empty() || !input.clicked.is_empty() || input.wheel != 0.0;
+        Self {
+            dt: input.dt,
+            keys: names(&input.pressed),
+            held: names(&input.held),
+            clicks: buttons(&input.clicked),
+            buttons: buttons(&input.buttons_down),
+            cursor: input.cursor.map(|c| [c.x, c.y, c.z]),
+            wheel: input.wheel,
+            text: input.text,
+            screen: input.screen.map(|s| s.to_array()),
+            pointer: pointer_used.then(|| input.pointer.to_array()),
+        }
+    }
+}
+
+impl TryFrom<FrameRecord> for FrameInput {
+    type Error = String;
+
+    fn try_from(record: FrameRecord) -> Result<Self, String> {
+        let keys = |names: &[String], known: &[KeyCode]| {
+            names
+                .iter()
+                .map(|name| {
+                    known
+                        .iter()
+                        .copied()
+                        .find(|k| format!("{:?}", k) == *name)
+                        .ok_or_else(|| format!("unknown key '{}'", name))
+                })
+                .collect::<Result<Vec<_>, String>>()
+        };
+        let buttons = |names: &[String]| {
+            names
+                .iter()
+                .map(|name| {
+                    BUTTONS
+                        .iter()
+                        .copied()
+                        .find(|b| format!("{:?}", b) == *name)
+                        .ok_or_else(|| format!("unknown mouse button '{}'", name))
+                })
+                .collect::<Result<Vec<_>, String>>()
+        };
+        let all_keys: Vec<KeyCode> = KEYS.iter().copied().chain(HEURISTIC_KEYS).collect();
+
+        Ok(Self {
+            dt: record.dt,
+            pressed: keys(&record.keys, &all_keys)?,
+            held: keys(&record.held, MODIFIERS)?,
+            clicked: buttons(&record.clicks)?,
+            buttons_down: buttons(&record.buttons)?,
+            cursor: record.cursor.map(|[x, y, z]| Node::at(x, y, z)),
+            pointer: record.pointer.map(Vec2::from_array).unwrap_or_default(),
+            wheel: record.wheel,
+            text: record.text,
+            screen: record.screen.map(Vec2::from_array),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_round_trip_through_json() {
+        let frame = FrameInput {
+            dt: 0.016,
+            pressed: vec![KeyCode::Space, KeyCode::F7, HEURISTIC_KEYS[0]],
+            held: vec![KeyCode::LeftShift],
+            clicked: vec![MouseButton::Left],
+            buttons_down: vec![MouseButton::Left, MouseButton::Right],
+            cursor: Some(Node::at(3, 5, 1)),
+            pointer: vec2(120.5, 64.0),
+            wheel: -1.0,
+            text: "max(dx\"".to_string(),
+            screen: Some(vec2(800.0, 600.0)),
+        };
+        let text = serde_json::to_string(&frame).unwrap();
+        assert!(text.contains("\"dt\":0.016"));
+        let back: FrameInput = serde_json::from_str(&text).unwrap();
+        assert_eq!(format!("{:?}", back), format!("{:?}", frame));
+    }
+
+    #[test]
+    fn quiet_frames_log_only_dt() {
+        let frame = FrameInput {
+            dt: 0.5,
+            pointer: vec2(1.0, 2.0),
+            ..Default::default()
+        };
+        assert_eq!(serde_json::to_string(&frame).unwrap(), r#"{"dt":0.5}"#);
+        let back: FrameInput = serde_json::from_str(r#"{"dt":0.5}"#).unwrap();
+        assert_eq!(back.pointer, Vec2::ZERO);
+    }
+
+    #[test]
+    fn unknown_names_and_missing_dt_are_errors() {
+        let parse = |text: &str| {
+            serde_json::from_str::<FrameInput>(text)
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(parse(r#"{"dt":0.1,"keys":["Q"]}"#).contains("unknown key 'Q'"));
+        assert!(parse(r#"{"dt":0.1,"held":["Space"]}"#).contains("unknown key 'Space'"));
+        assert!(parse(r#"{"dt":0.1,"clicks":["Back"]}"#).contains("unknown mouse button 'Back'"));
+        assert!(parse(r#"{"keys":[]}"#).contains("missing field `dt`"));
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use ::rand::prelude::IndexedRandom;
+use macroquad::prelude::*;
+
+use crate::algorithm::AStarVisualizer;
+use crate::maze::Maze;
+use crate::node::Node;
+use crate::rng;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Selection {
+    Farthest, // Each landmark as far as possible from the start and the ones before it
+    Random,
+}
+
+impl Selection {
+    pub const ALL: [Selection; 2] = [Selection::Farthest, Selection::Random];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Selection::Farthest => "Farthest",
+            Selection::Random => "Random",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|s| s.label().eq_ignore_ascii_case(name))
+    }
+}
+
+// True costs from a few landmark cells to every cell they reach. For any landmark L,
+// |d(L, b) - d(L, a)| <= d(a, b) by the triangle inequality, so the largest such gap is
+// an admissible estimate that follows the corridors instead of the straight line (ALT)
+#[derive(Default)]
+pub struct Landmarks {
+    pub nodes: Vec<Node>,
+    tables: Vec<HashMap<Node, f32>>,
+}
+
+impl Landmarks {
+    pub fn place(maze: &Maze, count: usize, selection: Selection) -> Self {
+        let reach = AStarVisualizer::distances(maze, &[maze.start]);
+        // Sorted, so ties and random picks don't depend on hash order
+        let mut cells: Vec<Node> = reach.keys().copied().collect();
+        cells.sort_by_key(|n| (n.z, n.y, n.x));
+
+        let nodes: Vec<Node> = match selection {
+            Selection::Random => cells
+                .choose_multiple(&mut rng::rng(), count)
+                .copied()
+                .collect(),
+            Selection::Farthest => {
+                let mut nodes = Vec::new();
+                let mut nearest = reach;
+                while nodes.len() < count {
+                    let Some(&far) = cells
+                        .iter()
+                        .filter(|n| !nodes.contains(*n))
+                        .max_by(|a, b| nearest[*a].total_cmp(&nearest[*b]))
+                    else {
+                        break;
+                    };
+                    nodes.push(far);
+                    for (node, d) in AStarVisualizer::distances(maze, &[far]) {
+                        nearest.entry(node).and_modify(|n| *n = n.min(d));
+                    }
+                }
+                nodes
+            }
+        };
+        let tables = nodes
+            .iter()
+            .map(|&n| AStarVisualizer::distances(maze, &[n]))
+            .collect();
+        Self { nodes, tables }
+    }
+
+    pub fn estimate(&self, a: Node, b: Node) -> f32 {
+        self.tables
+            .iter()
+            .filter_map(|t| Some((t.get(&a)? - t.get(&b)?).abs()))
+            .fold(0.0, f32::max)
+    }
+
+    pub fn draw(&self, maze: &Maze, cell_size: f32) {
+        let radius = maze.view_cell_size(cell_size) * 0.6;
+        for &node in &self.nodes {
+            if maze.visible(node.z) {
+                let c = maze.center(node, cell_size);
+                draw_poly_lines(c.x, c.y, 4, radius, 0.0, radius / 4.0, YELLOW);
+            }
+        }
+    }
+}
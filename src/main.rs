@@ -5,50 +5,134 @@
 mod agent;
 mod algorithm;
 mod app_state;
+mod ara;
+mod check;
+mod cli;
 mod config;
 mod constants;
+mod editor;
+mod export;
+mod expr;
 mod generator;
 mod helper;
 mod heuristic;
+mod input;
+mod landmarks;
+mod mask;
 mod maze;
 mod node;
 mod obstacle;
+mod prompt;
+mod race;
+mod recorder;
+mod rng;
+mod scenario;
+mod session;
+mod settings;
+mod topology;
+mod tour;
+mod view;
 
 use macroquad::prelude::*;
 use std::time::{Duration, Instant};
 
-use crate::algorithm::AStarVisualizer;
-use crate::app_state::{AppMode, AppState};
+use crate::algorithm::{AStarVisualizer, ScoreOverlay};
+use crate::app_state::{AppMode, AppState, GoalMode};
+use crate::cli::Command;
 use crate::config::AppConfig;
 use crate::constants::*;
-use crate::generator::{Algorithm, MazeVisualizer};
+use crate::export::Figure;
+use crate::generator::Algorithm;
 use crate::heuristic::*;
-use crate::maze::{Maze, Tile};
+use crate::input::FrameInput;
+use crate::maze::{FloorView, Maze, Tile};
 use crate::node::Node;
+use crate::prompt::Prompt;
+use crate::scenario::Scenario;
+use crate::session::SessionLog;
+use crate::settings::{Settings, SettingsAction};
+use crate::topology::Topology;
 
-fn window_conf() -> Conf {
+fn window_conf(config: &AppConfig) -> Conf {
     Conf {
         window_title: "Maze Generator".to_owned(),
-        window_width: WINDOW_WIDTH,
-        window_height: WINDOW_HEIGHT,
+        window_width: config.screen_width as i32,
+        window_height: config.screen_height as i32,
+        window_resizable: true,
         ..Default::default()
     }
 }
 
-#[macroquad::main(window_conf)]
-async fn main() {
-    let config = AppConfig::default();
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = cli::parse(&args).unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, cli::USAGE);
+        std::process::exit(2);
+    });
+
+    match command {
+        Command::Record {
+            config,
+            path,
+            every,
+        } => {
+            if let Some(seed) = config.seed {
+                rng::reseed(seed);
+            }
+            if let Err(err) = recorder::record_session(&config, path, every) {
+                eprintln!("Recording failed: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Command::Run {
+            config,
+            scenario,
+            replay,
+        } => macroquad::Window::from_config(
+            window_conf(&config),
+            run(config, scenario, replay.map(|log| *log)),
+        ),
+    }
+}
+
+async fn run(config: AppConfig, scenario: Option<String>, replay: Option<SessionLog>) {
+    // The seed goes first: the initial maze is already drawn from it
+    if let Some(seed) = replay.as_ref().map(|log| log.seed).or(config.seed) {
+        rng::reseed(seed);
+    }
+    let mut session = SessionLog::new(rng::seed(), config.clone());
+    // A replay starts from the scenario it logged instead of reading the file again
+    let startup = match &replay {
+        Some(log) => log
+            .scenario
+            .clone()
+            .map(|s| ("the logged scenario".to_string(), Ok(s))),
+        None => scenario.map(|path| {
+            let scenario = Scenario::read(&path);
+            (path, scenario)
+        }),
+    };
+    let mut replay = replay.map(SessionLog::replay).unwrap_or_default();
+
     let mut app_state = AppState::new(config);
 
     // Initial setup
-    let mut current_algo = Algorithm::RecursiveBacktracker;
-    app_state.generator = Some(MazeVisualizer::new(&mut app_state.maze, current_algo));
-    app_state.mode = AppMode::MazeGeneration;
+    app_state.start_generation();
 
-    let mut current_heuristic: fn(Node, Node) -> f32 = manhattan;
-    let mut heuristic_name = "Manhattan";
+    if let Some((name, scenario)) = startup {
+        match scenario.and_then(|s| s.apply(&mut app_state).map(|()| s)) {
+            Ok(scenario) => session.scenario = Some(scenario),
+            Err(err) => eprintln!("Failed to load {}: {}", name, err),
+        }
+    }
 
     let mut time_accumulator = 0f64;
+    let mut race_accumulator = 0f64;
+    let mut ara_accumulator = 0f64;
+    let mut settings = Settings::new(&app_state.config);
+    let mut prompt = Prompt::default();
+    let mut formula_text = DEFAULT_FORMULA.to_string();
+    let mut formula_index: Option<usize> = None; // Where the typed heuristic is registered
     let mut start_time = Instant::now();
     let mut elapsed_duration = Duration::ZERO;
     let mut steps_count = 0;
@@ -58,223 +142,566 @@ async fn main() {
 
         let mut reset = false;
 
-        // Input Handling
-        for (i, (key, func, name)) in HEURISTIC.iter().enumerate() {
-            if is_key_pressed(*key) {
-                current_heuristic = *func;
-                heuristic_name = *name;
+        // Window resized: cells grow or shrink so the maze fills the new maze area. The
+        // size is logged with the frame, so a replay follows the session, not the window
+        let replaying = !replay.is_empty();
+        let window = vec2(screen_width(), screen_height());
+        let screen = match replay.front() {
+            Some(frame) => frame.screen,
+            None => (window
+                != vec2(
+                    app_state.config.screen_width,
+                    app_state.config.screen_height,
+                ))
+            .then_some(window),
+        };
+        if let Some(size) = screen {
+            app_state.config.screen_width = size.x;
+            app_state.config.screen_height = size.y;
+            app_state.fit_view();
+        }
 
-                // Mode 1: Update Solver (Classic)
-                if let AppMode::Pathfinding = app_state.mode {
-                    // Check if we are focusing on Solver (no agents?) or just update solver anyway
-                    app_state.solver = Some(AStarVisualizer::new(&app_state.maze));
-                    time_accumulator = 0.0;
-                    start_time = Instant::now();
-                    elapsed_duration = Duration::ZERO;
-                    steps_count = 0;
+        // Input Handling: logged frames first when replaying, then the live input.
+        // Replayed frames are logged again so a saved session stays complete
+        let input = replay.pop_front().unwrap_or_else(|| FrameInput {
+            screen,
+            ..FrameInput::capture(
+                &app_state.maze,
+                app_state.config.cell_size,
+                &app_state.view,
+                app_state.config.maze_area(),
+            )
+        });
+        if session.push(input.clone()) {
+            eprintln!(
+                "Session log full after {} frames; later input isn't logged",
+                MAX_SESSION_FRAMES
+            );
+        }
+
+        // While the formula prompt is open it takes the keyboard; Enter compiles the
+        // formula into a heuristic, selects it and checks it against true costs
+        let mut formula_picked = None;
+        let input = if prompt.open {
+            if let Some(text) = prompt.update(&input) {
+                match Formula::new(&text) {
+                    Ok(formula) => {
+                        let i = match formula_index {
+                            Some(i) => {
+                                app_state.heuristics.replace(i, formula);
+                                i
+                            }
+                            None => app_state.heuristics.register(formula),
+                        };
+                        app_state.prepare_heuristic(i);
+                        if !matches!(app_state.mode, AppMode::MazeGeneration) {
+                            app_state.check = Some(app_state.new_check(i));
+                        }
+                        formula_index = Some(i);
+                        formula_picked = Some(i);
+                        formula_text = text;
+                        prompt.close();
+                    }
+                    Err(err) => prompt.error = Some(err),
                 }
+            }
+            input.without_keys()
+        } else {
+            input
+        };
+
+        // Mouse wheel zooms around the pointer, a middle drag pans, Home fits the maze
+        let area = app_state.config.maze_area();
+        if input.wheel != 0.0 && area.contains(input.pointer) && !settings.captures(&input) {
+            app_state
+                .view
+                .zoom_at(input.pointer, ZOOM_STEP.powf(input.wheel.signum()));
+        }
+        if input.key_pressed(KeyCode::Home) {
+            app_state.fit_view();
+        }
 
-                // Mode 2: Reset & Replath **Main Agent ONLY**
-                for agent in &mut app_state.agents {
-                    if agent.is_main {
-                        agent.heuristic_index = i; // Assign new heuristic
-                        agent.reset_to_start();
-
-                        // Main Agent Target (Fixed Bottom-Right)
-                        let target_node = Node::new(
-                            app_state.config.grid_width - 2,
-                            app_state.config.grid_height - 2,
-                        );
-                        let start_node = Node::new(
-                            agent.position.x.round() as usize,
-                            agent.position.y.round() as usize,
-                        );
+        // F1: Settings panel; it gets first pick of the mouse while open
+        if input.key_pressed(KeyCode::F1) {
+            settings.toggle(&app_state.config);
+        }
+        match settings.update(&input, &mut app_state.config) {
+            Some(SettingsAction::Resize { width, height }) => {
+                app_state.config.grid_width = width;
+                app_state.config.grid_height = height;
+                if let Topology::Polar { .. } = app_state.config.topology {
+                    app_state.config.topology = Topology::polar(width, height);
+                }
+                reset = true;
+            }
+            Some(SettingsAction::Regenerate) => reset = true,
+            Some(SettingsAction::Replan) => app_state.replan(),
+            Some(SettingsAction::Remap) => {
+                app_state.maze.connectivity = app_state.config.connectivity;
+                app_state.maze_changed();
+                app_state.replan();
+            }
+            Some(SettingsAction::Retune) => app_state.apply_tuning(),
+            None => {}
+        }
+        let over_panel = settings.captures(&input);
+        let mut middle_click = false;
+        if input.button_pressed(MouseButton::Middle) && area.contains(input.pointer) && !over_panel
+        {
+            app_state.view.begin_pan(input.pointer);
+        } else if input.button_down(MouseButton::Middle) {
+            app_state.view.drag(input.pointer);
+        } else if app_state.view.panning() {
+            middle_click = app_state.view.end_pan();
+        }
 
-                        if let Some(path) = AStarVisualizer::find_path(
-                            &app_state.maze,
-                            start_node,
-                            target_node,
-                            *func, // New heuristic
-                            &app_state.obstacles,
-                        ) {
-                            agent.set_path(path);
+        // In the race, number keys add / remove lanes instead
+        if app_state.race.is_some() {
+            for (i, key) in HEURISTIC_KEYS.into_iter().enumerate() {
+                if i < app_state.heuristics.len() && input.key_pressed(key) {
+                    let lanes = &mut app_state.race_heuristics;
+                    match lanes.iter().position(|&h| h == i) {
+                        Some(at) if lanes.len() > 1 => {
+                            lanes.remove(at);
+                        }
+                        Some(_) => {}
+                        None => {
+                            lanes.push(i);
+                            lanes.sort();
                         }
                     }
+                    app_state.race = Some(app_state.new_race());
+                }
+            }
+        }
+
+        let picked = app_state
+            .heuristics
+            .keys()
+            .find(|&(_, key)| input.key_pressed(key))
+            .map(|(i, _)| i)
+            .or(formula_picked);
+        if app_state.race.is_none()
+            && let Some(i) = picked
+        {
+            // Mode 1: Update Solver (Classic)
+            if let AppMode::Pathfinding = app_state.mode {
+                // Check if we are focusing on Solver (no agents?) or just update solver anyway
+                app_state.solver = Some(app_state.new_solver());
+                time_accumulator = 0.0;
+                start_time = Instant::now();
+                elapsed_duration = Duration::ZERO;
+                steps_count = 0;
+            }
+
+            // Mode 2: Reset & Replath **Main Agent ONLY**
+            app_state.heuristic = i;
+            app_state.prepare_heuristic(i);
+            if app_state.check.is_some() {
+                app_state.check = Some(app_state.new_check(i));
+            }
+            for idx in 0..app_state.agents.len() {
+                if app_state.agents[idx].is_main {
+                    app_state.agents[idx].heuristic_index = i; // Assign new heuristic
+                    app_state.agents[idx].reset_to_start();
+                    app_state.route_main_agent(idx);
                 }
-                break;
             }
         }
 
-        if is_key_pressed(KeyCode::R) {
-            current_algo = Algorithm::RecursiveBacktracker;
+        if input.key_pressed(KeyCode::R) {
+            app_state.config.algorithm = Algorithm::RecursiveBacktracker;
+            reset = true;
+        }
+        if input.key_pressed(KeyCode::P) {
+            app_state.config.algorithm = Algorithm::Prims;
+            reset = true;
+        }
+        if input.key_pressed(KeyCode::B) {
+            app_state.config.algorithm = Algorithm::Braid;
             reset = true;
         }
-        if is_key_pressed(KeyCode::P) {
-            current_algo = Algorithm::Prims;
+        if input.key_pressed(KeyCode::Space) {
             reset = true;
         }
-        if is_key_pressed(KeyCode::B) {
-            current_algo = Algorithm::Braid;
+        if input.key_pressed(KeyCode::E) {
+            app_state.config.algorithm = Algorithm::Eller;
             reset = true;
         }
-        if is_key_pressed(KeyCode::Space) {
+        if input.key_pressed(KeyCode::K) {
+            app_state.config.algorithm = Algorithm::Kruskal;
             reset = true;
         }
-        if is_key_pressed(KeyCode::E) {
-            current_algo = Algorithm::Eller;
+        // W Key: Toggle weave (passages tunnelling under each other)
+        if input.key_pressed(KeyCode::W) {
+            app_state.config.weave = !app_state.config.weave;
+            reset = true;
+        }
+        // H Key: Cycle square, hexagonal and polar (circular) grids
+        if input.key_pressed(KeyCode::H) {
+            app_state.config.topology = match app_state.config.topology {
+                Topology::Square => Topology::Hex,
+                Topology::Hex => {
+                    Topology::polar(app_state.config.grid_width, app_state.config.grid_height)
+                }
+                Topology::Polar { .. } => Topology::Square,
+            };
+            reset = true;
+        }
+        // F4 Key: Shape the maze with a mask image / ASCII art, or drop the mask
+        if input.key_pressed(KeyCode::F4) {
+            if app_state.maze.mask.take().is_none() {
+                match session.read_mask(&mut replay, MASK_FILES) {
+                    Ok(mask) => app_state.maze.mask = Some(mask),
+                    Err(err) => eprintln!("Failed to load mask: {}", err),
+                }
+            }
+            reset = true;
+        }
+        // L Key: Cycle the number of floors
+        if input.key_pressed(KeyCode::L) {
+            app_state.config.floors = app_state.config.floors % MAX_FLOORS + 1;
             reset = true;
         }
 
         if reset {
             app_state.reset_maze();
-            app_state.generator = Some(MazeVisualizer::new(&mut app_state.maze, current_algo));
-            app_state.mode = AppMode::MazeGeneration;
+            app_state.start_generation();
             start_time = Instant::now();
             elapsed_duration = Duration::ZERO;
             steps_count = 0;
             time_accumulator = 0.0;
         }
 
-        if is_key_pressed(KeyCode::A) {
-            if let AppMode::MazeGeneration = app_state.mode {
-                if let Some(generator) = &mut app_state.generator {
-                    while !generator.done {
-                        generator.step(&mut app_state.maze);
-                    }
-                }
+        if input.key_pressed(KeyCode::A)
+            && let AppMode::MazeGeneration = app_state.mode
+            && let Some(generator) = &mut app_state.generator
+        {
+            while !generator.done {
+                generator.step(&mut app_state.maze);
             }
         }
 
         // M Key: Toggle Mode (Classic Solver <-> Agent Sim)
-        if is_key_pressed(KeyCode::M) {
+        if input.key_pressed(KeyCode::M) {
             app_state.show_solver = !app_state.show_solver;
         }
 
-        // Interaction Control
-        if is_mouse_button_pressed(MouseButton::Left) {
-            let (mouse_x, mouse_y) = mouse_position();
-            let grid_x = (mouse_x / app_state.config.cell_size) as usize;
-            let grid_y = (mouse_y / app_state.config.cell_size) as usize;
-
-            if Maze::in_bounds(
-                grid_x as isize,
-                grid_y as isize,
-                app_state.config.grid_width,
-                app_state.config.grid_height,
-            ) {
-                if app_state.maze.grid[grid_y][grid_x] == Tile::Path {
-                    if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) {
-                        // Set Global Target
-                        app_state.global_target = Some(Node::new(grid_x, grid_y));
-                        // Re-route ALL agents immediately
-                        for agent in &mut app_state.agents {
-                            let start_node = Node::new(
-                                agent.position.x.round() as usize,
-                                agent.position.y.round() as usize,
-                            );
-                            if let Some(path) = AStarVisualizer::find_path(
-                                &app_state.maze,
-                                start_node,
-                                Node::new(grid_x, grid_y),
-                                current_heuristic,
-                                &app_state.obstacles,
-                            ) {
-                                agent.set_path(path);
-                                agent.target = None; // Force update
-                            }
-                        }
-                    } else {
-                        // Spawn Agent
-                        let mut agent =
-                            crate::agent::Agent::new(Node::new(grid_x, grid_y), RED, false);
+        // Enter: Pause / resume generation and search; Right steps one forward
+        if input.key_pressed(KeyCode::Enter) {
+            app_state.paused = !app_state.paused;
+        }
+        if input.key_pressed(KeyCode::Right)
+            && let AppMode::MazeGeneration = app_state.mode
+            && let Some(generator) = &mut app_state.generator
+        {
+            app_state.paused = true;
+            generator.step(&mut app_state.maze);
+        }
+        // - / =: Slower / faster stepping; [ / ]: Slower / faster agents and obstacles
+        if input.key_pressed(KeyCode::Minus) {
+            app_state.config.speed_down();
+        }
+        if input.key_pressed(KeyCode::Equal) {
+            app_state.config.speed_up();
+        }
+        if input.key_pressed(KeyCode::LeftBracket) {
+            app_state.config.scale_time(false);
+        }
+        if input.key_pressed(KeyCode::RightBracket) {
+            app_state.config.scale_time(true);
+        }
+        // , / .: Lower / raise the heuristic weight; the searches restart with it
+        if input.key_pressed(KeyCode::Comma) || input.key_pressed(KeyCode::Period) {
+            let step = if input.key_pressed(KeyCode::Period) {
+                WEIGHT_STEP
+            } else {
+                -WEIGHT_STEP
+            };
+            app_state.config.heuristic_weight =
+                (app_state.config.heuristic_weight + step).clamp(1.0, MAX_HEURISTIC_WEIGHT);
+            app_state.replan();
+        }
+        // Left / Right also step the search (or every race lane) back / forward;
+        // ARA* only steps forward
+        if let Some(ara) = &mut app_state.ara {
+            if input.key_pressed(KeyCode::Right) {
+                app_state.paused = true;
+                ara.step(
+                    &app_state.maze,
+                    app_state.heuristics.get(app_state.heuristic),
+                );
+            }
+        } else if let Some(race) = &mut app_state.race {
+            if input.key_pressed(KeyCode::Left) {
+                app_state.paused = true;
+                for lane in &mut race.lanes {
+                    lane.solver.step_back();
+                }
+            }
+            if input.key_pressed(KeyCode::Right) {
+                app_state.paused = true;
+                race.step(1, &app_state.heuristics);
+            }
+        } else if let Some(solver) = &mut app_state.solver
+            && app_state.show_solver
+        {
+            if input.key_pressed(KeyCode::Left) {
+                app_state.paused = true;
+                solver.step_back();
+            }
+            if input.key_pressed(KeyCode::Right) {
+                app_state.paused = true;
+                solver.step(
+                    &app_state.maze,
+                    app_state.heuristics.get(app_state.heuristic),
+                );
+            }
 
-                        let target_node = if let Some(gt) = app_state.global_target {
-                            gt
-                        } else {
-                            // Random Target
-                            let mut rng = ::rand::rng();
-                            use ::rand::Rng;
-                            let mut t = Node::new(1, 1);
-                            for _ in 0..50 {
-                                let tx = rng.random_range(1..app_state.config.grid_width - 1);
-                                let ty = rng.random_range(1..app_state.config.grid_height - 1);
-                                if app_state.maze.grid[ty][tx] == Tile::Path {
-                                    t = Node::new(tx, ty);
-                                    break;
-                                }
-                            }
-                            t
-                        };
+            // Dragging along the timeline scrubs through the recorded expansions
+            let timeline = timeline_rect(&app_state.config);
+            if input.button_down(MouseButton::Left) && timeline.contains(input.pointer) {
+                app_state.paused = true;
+                let t = (input.pointer.x - timeline.x) / timeline.w;
+                solver.seek((t * solver.history.len() as f32).round() as usize);
+            }
+        }
+
+        // C Key: Race the selected heuristics side by side on copies of the maze
+        if input.key_pressed(KeyCode::C) && !matches!(app_state.mode, AppMode::MazeGeneration) {
+            app_state.ara = None;
+            app_state.race = match app_state.race {
+                Some(_) => None,
+                None => Some(app_state.new_race()),
+            };
+        }
+        // I Key: Anytime search (ARA*), refining its path as the weight comes down to 1
+        if input.key_pressed(KeyCode::I) && !matches!(app_state.mode, AppMode::MazeGeneration) {
+            app_state.race = None;
+            app_state.ara = match app_state.ara {
+                Some(_) => None,
+                None => Some(app_state.new_ara()),
+            };
+        }
+
+        // F Key: Type a heuristic formula
+        if input.key_pressed(KeyCode::F) {
+            prompt.show(&formula_text);
+        }
+
+        // F9: Check the current heuristic against true costs to the goal
+        if input.key_pressed(KeyCode::F9) && !matches!(app_state.mode, AppMode::MazeGeneration) {
+            app_state.check = match app_state.check {
+                Some(_) => None,
+                None => Some(app_state.new_check(app_state.heuristic)),
+            };
+        }
+
+        // O Key: Cycle the g/h/f overlay on explored cells
+        if input.key_pressed(KeyCode::O) {
+            app_state.overlay = app_state.overlay.next();
+        }
+
+        // N Key: Cycle Goal Mode (Single -> Nearest -> Tour)
+        if input.key_pressed(KeyCode::N) {
+            app_state.goal_mode = app_state.goal_mode.next();
+            app_state.tour = None;
+            app_state.replan();
+            time_accumulator = 0.0;
+            start_time = Instant::now();
+            elapsed_duration = Duration::ZERO;
+            steps_count = 0;
+        }
+
+        // V Key: One floor at a time <-> all floors side by side
+        if input.key_pressed(KeyCode::V) {
+            app_state.maze.view = match app_state.maze.view {
+                FloorView::Single(_) => FloorView::All,
+                FloorView::All => FloorView::Single(0),
+            };
+        }
+        // PageUp / PageDown: Browse floors
+        if let FloorView::Single(floor) = app_state.maze.view {
+            let top = app_state.maze.floors - 1;
+            if input.key_pressed(KeyCode::PageUp) {
+                app_state.maze.view = FloorView::Single((floor + 1).min(top));
+            }
+            if input.key_pressed(KeyCode::PageDown) {
+                app_state.maze.view = FloorView::Single(floor.saturating_sub(1));
+            }
+        }
+
+        // T Key: Toggle Maze Editor
+        if input.key_pressed(KeyCode::T) {
+            app_state.editor.active = !app_state.editor.active;
+        }
+
+        // F2 / F3: Save / Load Maze (start and goal included)
+        if input.key_pressed(KeyCode::F2)
+            && let Err(err) = app_state.maze.save(MAZE_FILE)
+        {
+            eprintln!("Failed to save {}: {}", MAZE_FILE, err);
+        }
+        if input.key_pressed(KeyCode::F3) {
+            match session.read_maze(&mut replay, MAZE_FILE) {
+                Ok(maze) => {
+                    app_state.load_maze(maze);
+                    start_time = Instant::now();
+                    elapsed_duration = Duration::ZERO;
+                    steps_count = 0;
+                    time_accumulator = 0.0;
+                }
+                Err(err) => eprintln!("Failed to load {}: {}", MAZE_FILE, err),
+            }
+        }
+
+        // F6 / F7: Save / Load the whole scenario (maze, agents, obstacles, targets)
+        if input.key_pressed(KeyCode::F6)
+            && let Err(err) = scenario::save(&app_state, SCENARIO_FILE)
+        {
+            eprintln!("Failed to save {}: {}", SCENARIO_FILE, err);
+        }
+        if input.key_pressed(KeyCode::F7) {
+            match session
+                .read_scenario(&mut replay, SCENARIO_FILE)
+                .and_then(|s| s.apply(&mut app_state))
+            {
+                Ok(()) => {
+                    start_time = Instant::now();
+                    elapsed_duration = Duration::ZERO;
+                    steps_count = 0;
+                    time_accumulator = 0.0;
+                }
+                Err(err) => eprintln!("Failed to load {}: {}", SCENARIO_FILE, err),
+            }
+        }
+
+        // F8: Save the session log (seed + every frame's input) for --replay
+        if input.key_pressed(KeyCode::F8)
+            && !replaying
+            && let Err(err) = session.save(SESSION_FILE)
+        {
+            eprintln!("Failed to save {}: {}", SESSION_FILE, err);
+        }
+
+        // F5: Export the scene (all floors) as SVG and PNG figures
+        if input.key_pressed(KeyCode::F5) {
+            let figure = Figure::capture(
+                &app_state.maze,
+                app_state.solver.as_ref().filter(|_| app_state.show_solver),
+                &app_state.agents,
+                &app_state.obstacles,
+                EXPORT_CELL_SIZE,
+            );
+            if let Err(err) = figure.save_svg(EXPORT_SVG_FILE) {
+                eprintln!("Failed to export {}: {}", EXPORT_SVG_FILE, err);
+            }
+            if let Err(err) = figure.save_png(EXPORT_PNG_FILE) {
+                eprintln!("Failed to export {}: {}", EXPORT_PNG_FILE, err);
+            }
+        }
 
+        let editing = app_state.editor.active
+            && app_state.race.is_none()
+            && !over_panel
+            && !matches!(app_state.mode, AppMode::MazeGeneration);
+        // The race panels cover the maze, so clicks there don't reach it
+        let clicks_reach_maze = !editing && !over_panel && app_state.race.is_none();
+        if editing && app_state.editor.handle_input(&mut app_state.maze, &input) {
+            app_state.maze_changed();
+            app_state.replan();
+            time_accumulator = 0.0;
+            start_time = Instant::now();
+            elapsed_duration = Duration::ZERO;
+            steps_count = 0;
+        }
+
+        // Interaction Control
+        if clicks_reach_maze && input.button_pressed(MouseButton::Left) {
+            let clicked = input.cursor;
+
+            if let Some(clicked) = clicked
+                && app_state.maze[clicked] == Tile::Path
+            {
+                if input.shift() {
+                    // Set Global Target
+                    app_state.global_target = Some(clicked);
+                    // Re-route ALL agents immediately
+                    for agent in &mut app_state.agents {
                         if let Some(path) = AStarVisualizer::find_path(
                             &app_state.maze,
-                            Node::new(grid_x, grid_y),
-                            target_node,
-                            // Assign Random Heuristic for Crowd
-                            {
-                                let mut rng = ::rand::rng();
-                                use ::rand::Rng;
-                                let h_idx = rng.random_range(0..HEURISTIC.len());
-                                agent.heuristic_index = h_idx;
-                                HEURISTIC[h_idx].1
-                            },
+                            agent.grid_position(),
+                            clicked,
+                            app_state.heuristics.get(app_state.heuristic),
                             &app_state.obstacles,
                         ) {
                             agent.set_path(path);
-                            agent.initial_target =
-                                Some(vec2(target_node.x as f32, target_node.y as f32)); // Save for reset
-                            app_state.agents.push(agent);
+                            agent.target = None; // Force update
                         }
                     }
+                } else {
+                    // Spawn Agent
+                    let mut agent = crate::agent::Agent::new(clicked, RED, false);
+                    agent.speed = app_state.config.speed_of(false);
+                    agent.trail_length = app_state.config.trail_length;
+
+                    let target_node = if let Some(gt) = app_state.global_target {
+                        gt
+                    } else {
+                        // Random Target
+                        app_state.maze.random_open_cell(50)
+                    };
+
+                    // Assign Random Heuristic for Crowd
+                    let h_idx = {
+                        let mut rng = crate::rng::rng();
+                        use ::rand::Rng;
+                        rng.random_range(0..app_state.heuristics.len())
+                    };
+                    agent.heuristic_index = h_idx;
+                    app_state.prepare_heuristic(h_idx);
+
+                    if let Some(path) = AStarVisualizer::find_path(
+                        &app_state.maze,
+                        clicked,
+                        target_node,
+                        app_state.heuristics.get(h_idx),
+                        &app_state.obstacles,
+                    ) {
+                        agent.set_path(path);
+                        agent.initial_target = Some(target_node); // Save for reset
+                        app_state.agents.push(agent);
+                    }
                 }
             }
         }
 
         // Obstacle Spawning Control (Right Click)
-        if is_mouse_button_pressed(MouseButton::Right) {
-            let (mouse_x, mouse_y) = mouse_position();
-            let grid_x = (mouse_x / app_state.config.cell_size) as usize;
-            let grid_y = (mouse_y / app_state.config.cell_size) as usize;
-
-            if Maze::in_bounds(
-                grid_x as isize,
-                grid_y as isize,
-                app_state.config.grid_width,
-                app_state.config.grid_height,
-            ) {
-                if app_state.maze.grid[grid_y][grid_x] == Tile::Path {
-                    app_state
-                        .obstacles
-                        .push(crate::obstacle::DynamicObstacle::new(Node::new(
-                            grid_x, grid_y,
-                        )));
-                }
+        if clicks_reach_maze && input.button_pressed(MouseButton::Right) {
+            let clicked = input.cursor;
+
+            if let Some(clicked) = clicked
+                && app_state.maze[clicked] == Tile::Path
+            {
+                let mut obstacle =
+                    crate::obstacle::DynamicObstacle::new(clicked, app_state.maze.topology);
+                obstacle.move_interval = app_state.config.obstacle_interval;
+                app_state.obstacles.push(obstacle);
             }
         }
-        // Main Agent Spawning (Middle Click) - Fixed Scenario (TL -> BR)
-        if is_mouse_button_pressed(MouseButton::Middle) {
-            // Force Start at Top-Left (1, 1)
-            let start_grid = Node::new(1, 1);
-            // Force Goal at Bottom-Right
-            let goal_grid = Node::new(
-                app_state.config.grid_width - 2,
-                app_state.config.grid_height - 2,
-            );
-
-            let mut agent = crate::agent::Agent::new(start_grid, BLUE, true);
-
-            if let Some(path) = AStarVisualizer::find_path(
-                &app_state.maze,
-                start_grid,
-                goal_grid,
-                current_heuristic,
-                &app_state.obstacles,
-            ) {
-                agent.set_path(path);
-                agent.initial_target = Some(vec2(goal_grid.x as f32, goal_grid.y as f32));
-                // Set fixed start position for reset
-                agent.start_position = vec2(start_grid.x as f32, start_grid.y as f32);
-                app_state.agents.push(agent);
+        // Main Agent Spawning (Middle Click) - Maze Start -> Goal(s)
+        // (on release, so a middle drag can pan instead)
+        if clicks_reach_maze && middle_click {
+            let mut agent = crate::agent::Agent::new(app_state.maze.start, BLUE, true);
+            agent.heuristic_index = app_state.heuristic;
+            agent.speed = app_state.config.speed_of(true);
+            agent.trail_length = app_state.config.trail_length;
+            app_state.agents.push(agent);
+
+            let idx = app_state.agents.len() - 1;
+            app_state.route_main_agent(idx);
+            if app_state.agents[idx].grid_path.is_empty() {
+                app_state.agents.pop();
             }
         }
 
@@ -282,12 +709,12 @@ async fn main() {
         match app_state.mode {
             AppMode::MazeGeneration => {
                 if let Some(generator) = &mut app_state.generator {
-                    if !generator.done {
+                    if !generator.done && !app_state.paused {
                         elapsed_duration = start_time.elapsed();
-                        let delay = STEP_DELAY_SEC;
+                        let delay = app_state.config.step_delay;
 
                         if delay <= 0.0001 {
-                            for _ in 0..MAZE_GEN_STEPS_PER_FRAME {
+                            for _ in 0..app_state.config.simulation_speed {
                                 generator.step(&mut app_state.maze);
                                 steps_count += 1;
                                 if generator.done {
@@ -296,7 +723,7 @@ async fn main() {
                             }
                             time_accumulator = 0.0;
                         } else {
-                            time_accumulator += get_frame_time() as f64;
+                            time_accumulator += input.dt as f64;
                             if time_accumulator >= delay {
                                 generator.step(&mut app_state.maze);
                                 steps_count += 1;
@@ -307,6 +734,7 @@ async fn main() {
 
                     if generator.done {
                         app_state.mode = AppMode::Pathfinding;
+                        app_state.maze_changed();
                         // app_state.solver = Some(AStarVisualizer::new(&app_state.maze)); // Disable auto solver for now, let agents roam
                         start_time = Instant::now();
                         elapsed_duration = Duration::ZERO;
@@ -315,7 +743,7 @@ async fn main() {
                 }
             }
             AppMode::Pathfinding | AppMode::Idle => {
-                let dt = get_frame_time();
+                let dt = input.dt * app_state.config.time_scale;
 
                 // Update Obstacles
                 for obstacle in &mut app_state.obstacles {
@@ -323,13 +751,8 @@ async fn main() {
                 }
 
                 // Collect positions of all agents for avoidance logic
-                let agent_positions: Vec<Node> = app_state
-                    .agents
-                    .iter()
-                    .map(|a| {
-                        Node::new(a.position.x.round() as usize, a.position.y.round() as usize)
-                    })
-                    .collect();
+                let agent_positions: Vec<Node> =
+                    app_state.agents.iter().map(|a| a.grid_position()).collect();
 
                 // Update Agents with Avoidance
                 for i in 0..app_state.agents.len() {
@@ -340,21 +763,21 @@ async fn main() {
                         let agent = &app_state.agents[i];
                         // If agent is stationary (no target) and wants to move (has path)
                         // It effectively "waits" at the current node if the NEXT node is occupied
-                        if agent.target.is_none() {
-                            if let Some(next_node) = agent.grid_path.front() {
-                                let next_pos = vec2(next_node.x as f32, next_node.y as f32);
-                                // Check collision with other agents
-                                for (j, other) in app_state.agents.iter().enumerate() {
-                                    if i != j {
-                                        // If other agent is at the target cell or moving to it
-                                        // Simple distance check covers both roughly
-                                        if other.position.distance(next_pos) < 0.9 {
-                                            blocked = true;
-                                            if agent.is_main {
-                                                repath_needed = true;
-                                            }
-                                            break;
+                        if agent.target.is_none()
+                            && let Some(next_node) = agent.grid_path.front()
+                        {
+                            let next_pos = next_node.position();
+                            // Check collision with other agents
+                            for (j, other) in app_state.agents.iter().enumerate() {
+                                if i != j {
+                                    // If other agent is at the target cell or moving to it
+                                    // Simple distance check covers both roughly
+                                    if other.position.distance(next_pos) < 0.9 {
+                                        blocked = true;
+                                        if agent.is_main {
+                                            repath_needed = true;
                                         }
+                                        break;
                                     }
                                 }
                             }
@@ -368,35 +791,26 @@ async fn main() {
                         // Main Agent "Dodge" Logic (Immediate)
                         if repath_needed {
                             // Try to find a detour around the blockage
-                            let target_node = if let Some(t) = agent.initial_target {
-                                Node::new(t.x as usize, t.y as usize)
-                            } else {
-                                Node::new(
-                                    app_state.config.grid_width - 2,
-                                    app_state.config.grid_height - 2,
-                                )
-                            };
-
-                            let start_node =
-                                Node::new(agent.position.x as usize, agent.position.y as usize);
-                            let h_func = HEURISTIC
-                                .get(agent.heuristic_index)
-                                .map(|x| x.1)
-                                .unwrap_or(manhattan);
+                            let goals = app_state.goal_mode.targets(&app_state.maze, agent);
+
+                            let start_node = agent.grid_position();
+                            let h_func = app_state.heuristics.get(agent.heuristic_index);
 
                             // Build obstacles including ALL agents (Snapshot)
                             let mut loop_obstacles = app_state.obstacles.clone();
                             for (idx, pos) in agent_positions.iter().enumerate() {
                                 if idx != i {
-                                    loop_obstacles
-                                        .push(crate::obstacle::DynamicObstacle::new(*pos));
+                                    loop_obstacles.push(crate::obstacle::DynamicObstacle::new(
+                                        *pos,
+                                        app_state.maze.topology,
+                                    ));
                                 }
                             }
 
-                            if let Some(path) = AStarVisualizer::find_path(
+                            if let Some(path) = AStarVisualizer::find_path_to_any(
                                 &app_state.maze,
                                 start_node,
-                                target_node,
+                                &goals,
                                 h_func,
                                 &loop_obstacles,
                             ) {
@@ -409,24 +823,10 @@ async fn main() {
                             agent.blocked_time = 0.0;
 
                             // Random New Target
-                            let mut rng = ::rand::rng();
-                            use ::rand::Rng;
-                            let mut t = Node::new(1, 1);
-                            for _ in 0..20 {
-                                let tx = rng.random_range(1..app_state.config.grid_width - 1);
-                                let ty = rng.random_range(1..app_state.config.grid_height - 1);
-                                if app_state.maze.grid[ty][tx] == Tile::Path {
-                                    t = Node::new(tx, ty);
-                                    break;
-                                }
-                            }
+                            let t = app_state.maze.random_open_cell(20);
 
-                            let start_node =
-                                Node::new(agent.position.x as usize, agent.position.y as usize);
-                            let h_func = HEURISTIC
-                                .get(agent.heuristic_index)
-                                .map(|x| x.1)
-                                .unwrap_or(manhattan);
+                            let start_node = agent.grid_position();
+                            let h_func = app_state.heuristics.get(agent.heuristic_index);
 
                             if let Some(path) = AStarVisualizer::find_path(
                                 &app_state.maze,
@@ -447,11 +847,7 @@ async fn main() {
 
                     // Basic collision with obstacles
                     for obs in &app_state.obstacles {
-                        if agent
-                            .position
-                            .distance(vec2(obs.position.x as f32, obs.position.y as f32))
-                            < 1.0
-                        {
+                        if agent.position.distance(obs.position.position()) < 1.0 {
                             agent.target = None;
                             agent.grid_path.clear();
                         }
@@ -459,55 +855,44 @@ async fn main() {
 
                     // If agent reached target (no more path), give new target
                     if agent.target.is_none() && agent.grid_path.is_empty() {
-                        let target_node = if agent.is_main {
-                            // Main Agent logic: Keep going to Goal (W-2, H-2) unless Global overrides
-                            if let Some(t) = agent.initial_target {
-                                Node::new(t.x as usize, t.y as usize)
-                            } else {
-                                Node::new(
-                                    app_state.config.grid_width - 2,
-                                    app_state.config.grid_height - 2,
-                                )
+                        let goals = if agent.is_main {
+                            // Main Agent logic: Drop tour stops it already stands on,
+                            // then keep going to the goal(s) of the current mode
+                            while let Some(stop) = agent.waypoints.front() {
+                                if agent.position.distance(stop.position()) < 1.5 {
+                                    agent.waypoints.pop_front();
+                                } else {
+                                    break;
+                                }
                             }
+                            app_state.goal_mode.targets(&app_state.maze, agent)
                         } else if let Some(gt) = app_state.global_target {
                             // ... existing Global Target logic ...
                             let dist = (agent.position.x - gt.x as f32).abs()
-                                + (agent.position.y - gt.y as f32).abs();
+                                + (agent.position.y - gt.y as f32).abs()
+                                + (agent.position.z - gt.z as f32).abs();
                             if dist < 1.0 {
                                 continue; // Finished
                             }
-                            gt
+                            vec![gt]
                         } else {
                             // Random Logic (Wander) for Spawn Agents
-                            let mut rng = ::rand::rng();
-                            use ::rand::Rng;
-                            let mut t = Node::new(1, 1);
-                            for _ in 0..20 {
-                                let tx = rng.random_range(1..app_state.config.grid_width - 1);
-                                let ty = rng.random_range(1..app_state.config.grid_height - 1);
-                                if app_state.maze.grid[ty][tx] == Tile::Path {
-                                    t = Node::new(tx, ty);
-                                    break;
-                                }
-                            }
-                            t
+                            vec![app_state.maze.random_open_cell(20)]
                         };
 
                         // Main Agent: If reached goal, stop (don't re-path to same goal continuously)
                         if agent.is_main {
-                            let dist = agent
-                                .position
-                                .distance(vec2(target_node.x as f32, target_node.y as f32));
-                            if dist < 1.5 {
+                            let arrived = goals.is_empty()
+                                || goals
+                                    .iter()
+                                    .any(|g| agent.position.distance(g.position()) < 1.5);
+                            if arrived {
                                 continue; // Arrived.
                             }
                         }
 
                         // Determine Heuristic
-                        let h_func = HEURISTIC
-                            .get(agent.heuristic_index)
-                            .map(|x| x.1)
-                            .unwrap_or(manhattan);
+                        let h_func = app_state.heuristics.get(agent.heuristic_index);
 
                         // Build Extended Obstacles for Main Agent
                         let mut loop_obstacles = app_state.obstacles.clone(); // Base obstacles
@@ -516,18 +901,19 @@ async fn main() {
                             for (idx, pos) in agent_positions.iter().enumerate() {
                                 if idx != i {
                                     // Don't block self
-                                    loop_obstacles
-                                        .push(crate::obstacle::DynamicObstacle::new(*pos));
+                                    loop_obstacles.push(crate::obstacle::DynamicObstacle::new(
+                                        *pos,
+                                        app_state.maze.topology,
+                                    ));
                                 }
                             }
                         }
 
-                        let start_node =
-                            Node::new(agent.position.x as usize, agent.position.y as usize);
-                        if let Some(path) = AStarVisualizer::find_path(
+                        let start_node = agent.grid_position();
+                        if let Some(path) = AStarVisualizer::find_path_to_any(
                             &app_state.maze,
                             start_node,
-                            target_node,
+                            &goals,
                             h_func,
                             &loop_obstacles,
                         ) {
@@ -536,118 +922,205 @@ async fn main() {
                     }
                 }
 
-                if app_state.show_solver {
-                    if let Some(solver) = &mut app_state.solver {
-                        if !solver.found {
-                            elapsed_duration = start_time.elapsed();
-                            let delay = STEP_DELAY_SEC;
-
-                            if delay <= 0.0001 {
-                                time_accumulator += get_frame_time() as f64;
-                                let steps = app_state.config.simulation_speed;
-
-                                for _ in 0..steps {
-                                    solver.step(&app_state.maze, current_heuristic);
-                                    steps_count += 1;
-                                    time_accumulator = 0.0;
-                                    if solver.found {
-                                        elapsed_duration = start_time.elapsed();
-                                        break;
-                                    }
-                                }
-                            } else {
-                                time_accumulator += get_frame_time() as f64;
-                                if time_accumulator >= delay {
-                                    solver.step(&app_state.maze, current_heuristic);
-                                    steps_count += 1;
-                                    time_accumulator -= delay;
-                                }
+                if app_state.show_solver
+                    && !app_state.paused
+                    && let Some(solver) = &mut app_state.solver
+                    && !solver.found
+                {
+                    elapsed_duration = start_time.elapsed();
+                    let delay = app_state.config.step_delay;
+
+                    if delay <= 0.0001 {
+                        time_accumulator += input.dt as f64;
+                        let steps = app_state.config.simulation_speed;
+
+                        for _ in 0..steps {
+                            solver.step(
+                                &app_state.maze,
+                                app_state.heuristics.get(app_state.heuristic),
+                            );
+                            steps_count += 1;
+                            time_accumulator = 0.0;
+                            if solver.found {
+                                elapsed_duration = start_time.elapsed();
+                                break;
                             }
                         }
+                    } else {
+                        time_accumulator += input.dt as f64;
+                        if time_accumulator >= delay {
+                            solver.step(
+                                &app_state.maze,
+                                app_state.heuristics.get(app_state.heuristic),
+                            );
+                            steps_count += 1;
+                            time_accumulator -= delay;
+                        }
                     }
                 }
-            }
-        }
 
-        // Drawing
-        app_state.maze.draw(app_state.config.cell_size);
+                if !app_state.paused
+                    && let Some(ara) = &mut app_state.ara
+                    && !ara.done
+                {
+                    let delay = app_state.config.step_delay;
+                    if delay <= 0.0001 {
+                        for _ in 0..app_state.config.simulation_speed {
+                            ara.step(
+                                &app_state.maze,
+                                app_state.heuristics.get(app_state.heuristic),
+                            );
+                        }
+                    } else {
+                        ara_accumulator += input.dt as f64;
+                        while ara_accumulator >= delay {
+                            ara.step(
+                                &app_state.maze,
+                                app_state.heuristics.get(app_state.heuristic),
+                            );
+                            ara_accumulator -= delay;
+                        }
+                    }
+                }
 
-        if app_state.show_solver {
-            if let Some(solver) = &app_state.solver {
-                solver.draw(&app_state.maze, app_state.config.cell_size);
+                // Race lanes advance together, at the same speed as the solver
+                if !app_state.paused
+                    && let Some(race) = &mut app_state.race
+                    && !race.done()
+                {
+                    let delay = app_state.config.step_delay;
+                    if delay <= 0.0001 {
+                        race.step(app_state.config.simulation_speed, &app_state.heuristics);
+                    } else {
+                        race_accumulator += input.dt as f64;
+                        while race_accumulator >= delay {
+                            race.step(1, &app_state.heuristics);
+                            race_accumulator -= delay;
+                        }
+                    }
+                }
             }
         }
 
-        for obstacle in &app_state.obstacles {
-            obstacle.draw(app_state.config.cell_size);
-        }
-
-        for agent in &app_state.agents {
-            agent.draw(app_state.config.cell_size);
-        }
-
-        if let Some(gt) = app_state.global_target {
-            let cz = app_state.config.cell_size;
-            draw_rectangle(gt.x as f32 * cz, gt.y as f32 * cz, cz, cz, GOLD);
-            draw_circle(
-                gt.x as f32 * cz + cz / 2.0,
-                gt.y as f32 * cz + cz / 2.0,
-                cz / 1.5,
-                RED,
-            );
+        // Drawing
+        if let Some(race) = &app_state.race {
+            race.draw(area, app_state.config.cell_size, app_state.overlay);
         } else {
-            // Draw Fixed Goal (Bottom Right) if Main Agent exists
-            if app_state.agents.iter().any(|a| a.is_main) {
-                let gx = app_state.config.grid_width - 2;
-                let gy = app_state.config.grid_height - 2;
-                let cz = app_state.config.cell_size;
-                draw_rectangle(
-                    gx as f32 * cz,
-                    gy as f32 * cz,
-                    cz,
-                    cz,
-                    Color::new(0.0, 0.0, 0.5, 0.5),
-                ); // Faint Blue
-                draw_circle(
-                    gx as f32 * cz + cz / 2.0,
-                    gy as f32 * cz + cz / 2.0,
-                    cz / 3.0,
-                    BLUE,
-                );
-            }
+            draw_scene(&app_state, editing, input.cursor, app_state.heuristic);
         }
 
-        let found = app_state.solver.as_ref().map_or(false, |v| v.found);
+        let found = app_state.solver.as_ref().is_some_and(|v| v.found);
+        // Path cost rather than hop count: diagonals and tunnels cost more than 1
         let distance = app_state
             .solver
             .as_ref()
-            .map_or(0, |v| v.path.as_ref().map_or(0, |p| p.len()));
+            .and_then(|v| v.path.as_ref())
+            .map_or(0.0, |p| app_state.maze.path_cost(p));
 
         draw_dashboard(
             &app_state.config,
-            heuristic_name,
+            app_state.heuristics.get(app_state.heuristic),
             &app_state,
             elapsed_duration,
             steps_count,
             distance,
             found,
         );
+        settings.draw();
+        prompt.draw(app_state.config.maze_area());
 
         next_frame().await;
     }
 }
 
+// Maze, search, agents and markers in the single-maze view
+fn draw_scene(app_state: &AppState, editing: bool, cursor: Option<Node>, heuristic_index: usize) {
+    let view = &app_state.view;
+    set_camera(&view.camera());
+    app_state.maze.draw(
+        app_state.config.cell_size,
+        view.visible_rect(app_state.config.maze_area()),
+    );
+
+    if let Some(check) = &app_state.check {
+        check.draw(&app_state.maze, app_state.config.cell_size);
+    } else if let Some(ara) = &app_state.ara {
+        ara.draw(&app_state.maze, app_state.config.cell_size);
+    } else if app_state.show_solver
+        && let Some(solver) = &app_state.solver
+    {
+        solver.draw(
+            &app_state.maze,
+            app_state.config.cell_size,
+            app_state.overlay,
+            view.zoom,
+        );
+    }
+
+    app_state
+        .heuristics
+        .get(heuristic_index)
+        .draw(&app_state.maze, app_state.config.cell_size);
+
+    for obstacle in &app_state.obstacles {
+        obstacle.draw(&app_state.maze, app_state.config.cell_size);
+    }
+
+    if app_state.goal_mode == GoalMode::Tour
+        && let Some(tour) = &app_state.tour
+    {
+        tour.draw(&app_state.maze, app_state.config.cell_size);
+    }
+
+    if editing {
+        app_state
+            .editor
+            .draw(&app_state.maze, app_state.config.cell_size, cursor);
+    }
+
+    for agent in &app_state.agents {
+        agent.draw(&app_state.maze, app_state.config.cell_size);
+    }
+
+    let cz = app_state.config.cell_size;
+    let marker = app_state.maze.view_cell_size(cz);
+    if let Some(gt) = app_state.global_target {
+        app_state.maze.draw_tile(gt, cz, GOLD);
+        if app_state.maze.visible(gt.z) {
+            let c = app_state.maze.center(gt, cz);
+            draw_circle(c.x, c.y, marker / 1.5, RED);
+        }
+    } else {
+        // Draw Maze Goal if Main Agent exists
+        let goal = app_state.maze.goal;
+        if app_state.agents.iter().any(|a| a.is_main) && app_state.maze.visible(goal.z) {
+            app_state
+                .maze
+                .draw_tile(goal, cz, Color::new(0.0, 0.0, 0.5, 0.5)); // Faint Blue
+            let c = app_state.maze.center(goal, cz);
+            draw_circle(c.x, c.y, marker / 3.0, BLUE);
+        }
+    }
+    set_default_camera();
+}
+
+// Scrub bar for the search history, just under the solver stats row of the dashboard
+fn timeline_rect(config: &AppConfig) -> Rect {
+    let ui_y_start = config.maze_area().bottom();
+    Rect::new(20.0, ui_y_start + 87.0, config.screen_width - 40.0, 8.0)
+}
+
 fn draw_dashboard(
     config: &AppConfig,
-    heuristic_name: &str,
+    heuristic: &dyn Heuristic,
     app_state: &AppState,
     elapsed_duration: std::time::Duration,
     steps_count: usize,
-    distance: usize,
+    distance: f32,
     found: bool,
 ) {
-    let ui_y_start = config.grid_height as f32 * config.cell_size;
-    let ui_height = config.ui_height as f32;
+    let ui_y_start = config.maze_area().bottom();
+    let ui_height = config.ui_height;
     let screen_width = config.screen_width;
 
     // Background
@@ -679,24 +1152,71 @@ fn draw_dashboard(
         22.0,
         WHITE,
     );
+    draw_text(
+        "[Enter] Pause | [</>] Step | [-/=] Speed | [[/]] Time | [,/.] Weight",
+        text_x + 400.0,
+        current_y,
+        20.0,
+        CYAN,
+    );
     current_y += line_height;
 
     // -- ROW 2: Heuristic & Mode --
-    let mode_text = if app_state.config.simulation_speed > 15 {
-        "Fast"
-    } else {
-        "Normal"
+    let mode_text = format!(
+        "{}{} x{}",
+        app_state.config.speed_label(),
+        if app_state.paused { " (Paused)" } else { "" },
+        app_state.config.time_scale
+    );
+    let solver_status = match (app_state.show_solver, app_state.overlay) {
+        (false, _) => "Vis: OFF".to_string(),
+        (true, ScoreOverlay::Off) => "Vis: ON".to_string(),
+        (true, overlay) => format!("Vis: {}", overlay.label()),
     };
-    let solver_status = if app_state.show_solver {
-        "Vis: ON"
-    } else {
-        "Vis: OFF"
+
+    let mut grid_text = app_state.config.topology.label().to_string();
+    if app_state.config.weave {
+        grid_text.push_str(" Weave");
+    }
+    if app_state.maze.mask.is_some() {
+        grid_text.push_str(" Masked");
+    }
+    // The grid may not take the chosen generator; name the one that actually ran
+    if let Some(generator) = &app_state.generator
+        && generator.algorithm != app_state.config.algorithm
+    {
+        grid_text.push_str(&format!(
+            " ({} ran, not {})",
+            generator.algorithm.name(),
+            app_state.config.algorithm.name()
+        ));
+    }
+    let floor_text = match app_state.maze.view {
+        FloorView::Single(floor) => format!("{}/{}", floor + 1, app_state.maze.floors),
+        FloorView::All => format!("All {}", app_state.maze.floors),
+    };
+
+    let goal_text = match (&app_state.goal_mode, &app_state.tour) {
+        (GoalMode::Tour, Some(tour)) => format!("Tour (cost {:.1})", tour.cost),
+        (mode, _) => mode.label().to_string(),
     };
 
+    let weight = config.heuristic_weight;
+    let algo_text = if weight > 1.0 {
+        format!("{} w={:.2}", heuristic.name(), weight)
+    } else {
+        heuristic.name().to_string()
+    };
     draw_text(
         format!(
-            "Algo: {} | Speed: {} | {}",
-            heuristic_name, mode_text, solver_status
+            "Algo: {} | Grid: {} | Floor: {} | Speed: {} | {} | Goals: {} x{}",
+            algo_text,
+            grid_text,
+            floor_text,
+            mode_text,
+            solver_status,
+            goal_text,
+            app_state.maze.goals().len()
         )
         .as_str(),
         text_x,
@@ -707,14 +1227,120 @@ fn draw_dashboard(
     current_y += line_height;
 
     // -- ROW 3: Stats --
-    if app_state.show_solver {
+    if let Some(check) = &app_state.check {
+        draw_text(
+            format!(
+                "Check {}: {} | [F9] Close",
+                app_state.heuristics.get(check.heuristic_index).name(),
+                check.summary()
+            )
+            .as_str(),
+            text_x,
+            current_y,
+            22.0,
+            match (check.admissible(), check.consistent()) {
+                (true, true) => GREEN,
+                (true, false) => ORANGE,
+                (false, _) => RED,
+            },
+        );
+    } else if let Some(ara) = &app_state.ara {
+        let costs: Vec<String> = ara
+            .solutions
+            .iter()
+            .map(|s| format!("{:.1}", s.cost))
+            .collect();
+        // The proven bound needs an admissible heuristic; otherwise compare with Dijkstra
+        let latest = ara.solutions.last().map_or("searching".to_string(), |s| {
+            let quality = match (s.bound, ara.optimal) {
+                (Some(bound), _) => format!("within x{:.3} of optimal", bound),
+                (None, Some(best)) => format!(
+                    "x{:.3} of optimal, no bound as h may overestimate",
+                    s.cost / best.max(f32::EPSILON)
+                ),
+                (None, None) => "no bound as h may overestimate".to_string(),
+            };
+            format!(
+                "found at w={:.2} after {} exp, {}",
+                s.weight, s.expansions, quality
+            )
+        });
+        draw_text(
+            format!(
+                "ARA* w={:.2} | Exp: {} | Costs: {} | {} | [I] Close",
+                ara.weight,
+                ara.expansions,
+                if costs.is_empty() {
+                    "-".to_string()
+                } else {
+                    costs.join(" > ")
+                },
+                latest,
+            )
+            .as_str(),
+            text_x,
+            current_y,
+            22.0,
+            if ara.done { GREEN } else { LIGHTGRAY },
+        );
+    } else if let Some(race) = &app_state.race {
+        draw_text(
+            format!(
+                "Race: {} lanes | Optimal Cost: {} | [1-9] Toggle Lane | [C] Close",
+                race.lanes.len(),
+                race.optimal
+                    .map_or("-".to_string(), |c| format!("{:.1}", c)),
+            )
+            .as_str(),
+            text_x,
+            current_y,
+            22.0,
+            if race.done() { GREEN } else { LIGHTGRAY },
+        );
+    } else if app_state.editor.active {
+        draw_text(
+            format!(
+                "Edit: [L-Drag] Wall | [R-Drag] Erase | [S] Start | [G] Goal | [X] Extra Goal | [Ctrl+Z/Y] Undo {} / Redo {}",
+                app_state.editor.undo_len(),
+                app_state.editor.redo_len(),
+            )
+            .as_str(),
+            text_x,
+            current_y,
+            22.0,
+            YELLOW,
+        );
+    } else if app_state.show_solver {
         let status_color = if found { GREEN } else { LIGHTGRAY };
+        let (step, total) = app_state
+            .solver
+            .as_ref()
+            .map_or((0, 0), |s| (s.cursor, s.history.len()));
+        // Weighted A* promises a cost within (1 + e) = w of the optimum, but only when
+        // the heuristic never overestimates; otherwise just the ratio is shown
+        let admissible = heuristic.admissible(&app_state.maze);
+        let cost_text = match app_state.solver.as_ref().map(|s| (s.cost(), s.optimal)) {
+            Some((Some(cost), Some(best))) => format!(
+                " | Cost: {:.1} vs optimal {:.1} (x{:.3}{})",
+                cost,
+                best,
+                cost / best.max(f32::EPSILON),
+                match admissible {
+                    false => String::new(),
+                    true if cost <= best * weight + 1e-3 => format!(", bound x{:.2}", weight),
+                    true => format!(", bound x{:.2} broken", weight),
+                },
+            ),
+            _ => String::new(),
+        };
         draw_text(
             format!(
-                "Solver: {:.4}s | Steps: {} | Dist: {}",
+                "Solver: {:.4}s | Step: {}/{} | Dist: {:.1}{}",
                 elapsed_duration.as_secs_f32(),
-                steps_count,
+                step,
+                total,
                 distance,
+                cost_text,
             )
             .as_str(),
             text_x,
@@ -722,10 +1348,19 @@ fn draw_dashboard(
             22.0,
             status_color,
         );
+
+        if total > 0 {
+            let bar = timeline_rect(config);
+            let knob_x = bar.x + bar.w * step as f32 / total as f32;
+            draw_rectangle(bar.x, bar.y, bar.w, bar.h, DARKGRAY);
+            draw_rectangle(bar.x, bar.y, knob_x - bar.x, bar.h, SKYBLUE);
+            draw_rectangle(knob_x - 2.0, bar.y - 2.0, 4.0, bar.h + 4.0, WHITE);
+        }
     } else {
         // Find Main Agent stats
         let main_agent_stats = app_state.agents.iter().find(|a| a.is_main).map(|a| {
-            let dist_to_target = if let Some(t) = a.target.or(a.initial_target) {
+            let dist_to_target = if let Some(t) = a.target.or(a.initial_target.map(Node::position))
+            {
                 t.distance(a.position)
             } else {
                 0.0
@@ -753,9 +1388,12 @@ fn draw_dashboard(
 
     // -- ROW 4: Controls --
     // Column 1
-    draw_text("[1-6] Algorithm", text_x, current_y, 20.0, CYAN);
+    draw_text("[1-9/F] Algorithm", text_x, current_y, 20.0, CYAN);
     draw_text("[Space] New Maze", text_x + 160.0, current_y, 20.0, CYAN);
     draw_text("[M] Toggle Mode", text_x + 340.0, current_y, 20.0, CYAN);
+    draw_text("[T] Edit Maze", text_x + 520.0, current_y, 20.0, CYAN);
+    draw_text("[F2/F3] Save/Load", text_x + 680.0, current_y, 20.0, CYAN);
+    draw_text("[N] Goal Mode", text_x + 860.0, current_y, 20.0, CYAN);
 
     current_y += line_height;
     // Column 2
@@ -767,6 +1405,14 @@ fn draw_dashboard(
         20.0,
         ORANGE,
     );
+    draw_text("[H] Grid Shape", text_x + 480.0, current_y, 20.0, CYAN);
+    draw_text(
+        "[L/V/PgUp/PgDn] Floors",
+        text_x + 640.0,
+        current_y,
+        20.0,
+        CYAN,
+    );
 
     current_y += line_height;
     // Column 3
@@ -778,4 +1424,18 @@ fn draw_dashboard(
         20.0,
         ORANGE,
     );
+    draw_text(
+        "[K] Kruskal | [W] Weave",
+        text_x + 480.0,
+        current_y,
+        20.0,
+        CYAN,
+    );
+    draw_text(
+        "[F4] Mask | [F5] Export",
+        text_x + 720.0,
+        current_y,
+        20.0,
+        CYAN,
+    );
 }
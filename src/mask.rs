@@ -0,0 +1,91 @@
+use macroquad::prelude::*;
+use std::path::Path;
+use std::{fs, io};
+
+// Shape of the maze: which parts of the rectangle are carved at all.
+// Kept at its own resolution and sampled for whatever maze size is in use.
+#[derive(Clone, Debug)]
+pub struct Mask {
+    width: usize,
+    height: usize,
+    cells: Vec<bool>, // Row-major, true = inside the maze
+}
+
+impl Mask {
+    // PNG files by extension, anything else is read as ASCII art
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let is_png = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+
+        if is_png {
+            Self::from_png(&fs::read(path)?)
+        } else {
+            Self::from_ascii(&fs::read_to_string(path)?)
+        }
+    }
+
+    // Dark, opaque pixels are inside the maze
+    pub fn from_png(bytes: &[u8]) -> io::Result<Self> {
+        let image = Image::from_file_with_format(bytes, Some(ImageFormat::Png))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        let (width, height) = (image.width as usize, image.height as usize);
+
+        let cells = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let c = image.get_pixel(x as u32, y as u32);
+                let luminance = 0.299 * c.r + 0.587 * c.g + 0.114 * c.b;
+                c.a > 0.5 && luminance < 0.5
+            })
+            .collect();
+
+        Self::new(width, height, cells)
+    }
+
+    // Any character other than a space or '.' is inside the maze
+    pub fn from_ascii(text: &str) -> io::Result<Self> {
+        let rows: Vec<&str> = text.lines().collect();
+        let height = rows.len();
+        let width = rows.iter().map(|r| r.chars().count()).max().unwrap_or(0);
+
+        let mut cells = vec![false; width * height];
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                cells[y * width + x] = !matches!(c, ' ' | '.');
+            }
+        }
+
+        Self::new(width, height, cells)
+    }
+
+    // Rows of '#' (inside) and '.', read back by `from_ascii`
+    pub fn to_ascii(&self) -> Vec<String> {
+        self.cells
+            .chunks(self.width)
+            .map(|row| row.iter().map(|&c| if c { '#' } else { '.' }).collect())
+            .collect()
+    }
+
+    fn new(width: usize, height: usize, cells: Vec<bool>) -> io::Result<Self> {
+        if width == 0 || height == 0 || !cells.contains(&true) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "mask has no inside cells",
+            ));
+        }
+        Ok(Self {
+            width,
+            height,
+            cells,
+        })
+    }
+
+    // Samples the mask under the center of tile (x, y) of a maze_width x maze_height grid
+    pub fn contains(&self, x: usize, y: usize, maze_width: usize, maze_height: usize) -> bool {
+        let mx = ((x as f32 + 0.5) * self.width as f32 / maze_width as f32) as usize;
+        let my = ((y as f32 + 0.5) * self.height as f32 / maze_height as f32) as usize;
+        self.cells[my.min(self.height - 1) * self.width + mx.min(self.width - 1)]
+    }
+}
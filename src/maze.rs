@@ -3,10 +3,15 @@
 #![allow(unused_variables)]
 
 use macroquad::prelude::*;
-use std::vec;
+use std::collections::HashSet;
+use std::ops::{Index, IndexMut};
+use std::path::Path;
+use std::{fs, io, vec};
 
 use crate::constants::TILE_SIZE;
+use crate::mask::Mask;
 use crate::node::Node;
+use crate::topology::{Topology, polar_sectors};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Tile {
@@ -14,59 +19,797 @@ pub enum Tile {
     Wall,
     Start,
     Goal,
+    Stairs,
+    Bridge, // Lattice cell with a passage running over a tunnel
+    Tunnel, // Wall slot beside a bridge that a passage runs under
 }
 
+impl Tile {
+    pub fn color(self) -> Color {
+        match self {
+            Tile::Wall => PURPLE,
+            Tile::Path => BLACK,
+            Tile::Start => GREEN,
+            Tile::Goal => RED,
+            Tile::Stairs => BROWN,
+            Tile::Bridge => BLACK,
+            Tile::Tunnel => Color::new(0.2, 0.2, 0.25, 1.0),
+        }
+    }
+}
+
+// Which floors are on screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FloorView {
+    Single(usize),
+    All, // Every floor side by side, scaled down to fit
+}
+
+// Moves a search may take on a square grid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connectivity {
+    Four,
+    Eight, // Diagonals too, past a corner with at least one open side
+}
+
+impl Connectivity {
+    pub const ALL: [Connectivity; 2] = [Connectivity::Four, Connectivity::Eight];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Connectivity::Four => "4-way",
+            Connectivity::Eight => "8-way",
+        }
+    }
+
+    // "4-way" or just "4"
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| {
+            c.label().eq_ignore_ascii_case(name) || c.label().strip_suffix("-way") == Some(name)
+        })
+    }
+}
+
+#[derive(Clone)]
 pub struct Maze {
-    pub grid: Vec<Vec<Tile>>,
+    pub layers: Vec<Vec<Vec<Tile>>>, // [z][y][x]
     pub start: Node,
     pub goal: Node,
+    pub extra_goals: Vec<Node>,
     pub width: usize,
     pub height: usize,
+    pub floors: usize,
+    pub stairs: HashSet<Node>, // Lower end of each staircase, leading to z + 1
+    pub topology: Topology,
+    pub view: FloorView,
+    pub mask: Option<Mask>, // Tiles outside the mask are never carved
+    pub connectivity: Connectivity,
+}
+
+impl Index<Node> for Maze {
+    type Output = Tile;
+
+    fn index(&self, node: Node) -> &Tile {
+        &self.layers[node.z][node.y][node.x]
+    }
+}
+
+impl IndexMut<Node> for Maze {
+    fn index_mut(&mut self, node: Node) -> &mut Tile {
+        &mut self.layers[node.z][node.y][node.x]
+    }
 }
 
 impl Maze {
     pub fn new(w_size: usize, h_size: usize) -> Self {
+        Self::with_topology(w_size, h_size, Topology::Square)
+    }
+
+    pub fn with_topology(w_size: usize, h_size: usize, topology: Topology) -> Self {
+        Self::with_floors(w_size, h_size, 1, topology)
+    }
+
+    pub fn with_floors(w_size: usize, h_size: usize, floors: usize, topology: Topology) -> Self {
         let width = w_size;
         let height = h_size;
-        let grid = vec![vec![Tile::Wall; width]; height];
+        let floors = floors.max(1);
+        let layers = vec![vec![vec![Tile::Wall; width]; height]; floors];
 
-        let start = Node { x: 1, y: 1 };
-        let goal = Node {
-            x: width - 2,
-            y: height - 2,
+        // The goal starts on the top floor so the default route has to climb
+        let start = Node::at(1, 1, 0);
+        let goal = match topology {
+            // Opposite the start, on the outer ring
+            Topology::Polar { rings } => {
+                Node::at(polar_sectors(rings - 1) + 1, 2 * rings - 1, floors - 1)
+            }
+            _ => Node::at(width - 2, height - 2, floors - 1),
         };
 
         Maze {
-            grid,
+            layers,
             start,
             goal,
+            extra_goals: Vec::new(),
             width,
             height,
+            floors,
+            stairs: HashSet::new(),
+            topology,
+            view: FloorView::Single(0),
+            mask: None,
+            connectivity: Connectivity::Four,
+        }
+    }
+
+    pub fn set_start(&mut self, node: Node) {
+        if self[self.start] == Tile::Start {
+            let start = self.start;
+            self[start] = self.floor_tile(start);
+        }
+        self.start = node;
+        self[node] = Tile::Start;
+    }
+
+    pub fn set_goal(&mut self, node: Node) {
+        if self[self.goal] == Tile::Goal {
+            let goal = self.goal;
+            self[goal] = self.floor_tile(goal);
+        }
+        self.extra_goals.retain(|&g| g != node);
+        self.goal = node;
+        self[node] = Tile::Goal;
+    }
+
+    // What an open tile shows once a start/goal marker leaves it
+    fn floor_tile(&self, node: Node) -> Tile {
+        if self.has_stairs(node) {
+            Tile::Stairs
+        } else {
+            Tile::Path
+        }
+    }
+
+    // Primary goal first, then any extra goals in placement order
+    pub fn goals(&self) -> Vec<Node> {
+        let mut goals = vec![self.goal];
+        goals.extend(self.extra_goals.iter().copied());
+        goals
+    }
+
+    pub fn add_extra_goal(&mut self, node: Node) {
+        if node != self.goal && !self.extra_goals.contains(&node) {
+            self.extra_goals.push(node);
+            self[node] = Tile::Goal;
+        }
+    }
+
+    pub fn remove_extra_goal(&mut self, node: Node) {
+        if let Some(i) = self.extra_goals.iter().position(|&g| g == node) {
+            self.extra_goals.remove(i);
+            self[node] = self.floor_tile(node);
+        }
+    }
+
+    pub fn contains(&self, node: Node) -> bool {
+        node.z < self.floors && self.inside(node.x as isize, node.y as isize)
+    }
+
+    pub fn has_stairs(&self, node: Node) -> bool {
+        self.stairs.contains(&node)
+            || (node.z > 0 && self.stairs.contains(&Node::at(node.x, node.y, node.z - 1)))
+    }
+
+    // Tiles adjacent to `node` inside the border, walls included, plus the floors
+    // reachable by stairs. A tunnel leads straight under its bridge to the far side
+    pub fn neighbors(&self, node: Node) -> Vec<Node> {
+        let here = self[node];
+        let mut neighbors = Vec::new();
+        for dir in 0..self.topology.direction_count() {
+            // Polar wall rows reach the same outer tile both ways
+            let Some(next) = self.neighbor(node, dir).filter(|n| !neighbors.contains(n)) else {
+                continue;
+            };
+            match (here, self[next]) {
+                (Tile::Tunnel, Tile::Bridge) => {
+                    if let Some(exit) = self.neighbor(next, dir)
+                        && self[exit] == Tile::Tunnel
+                    {
+                        neighbors.push(exit);
+                    }
+                }
+                (Tile::Bridge, Tile::Tunnel) => {}
+                _ => neighbors.push(next),
+            }
+        }
+
+        if self.stairs.contains(&node) {
+            neighbors.push(Node::at(node.x, node.y, node.z + 1));
+        }
+        if node.z > 0 {
+            let below = Node::at(node.x, node.y, node.z - 1);
+            if self.stairs.contains(&below) {
+                neighbors.push(below);
+            }
+        }
+        neighbors
+    }
+
+    // Steps a search may take: the neighbors, plus the diagonals on an 8-way square
+    // grid. Generators and dead-end counts keep to `neighbors`
+    pub fn moves(&self, node: Node) -> Vec<Node> {
+        let mut moves = self.neighbors(node);
+        let crossing = |tile| matches!(tile, Tile::Bridge | Tile::Tunnel);
+        if self.connectivity == Connectivity::Four
+            || self.topology != Topology::Square
+            || crossing(self[node])
+        {
+            return moves;
+        }
+
+        let open =
+            |x: usize, y: usize| !matches!(self[Node::at(x, y, node.z)], Tile::Wall | Tile::Tunnel);
+        for (dx, dy) in [(-1, -1), (1, -1), (-1, 1), (1, 1)] {
+            let (x, y) = (node.x as isize + dx, node.y as isize + dy);
+            if !self.inside(x, y) {
+                continue;
+            }
+            let (x, y) = (x as usize, y as usize);
+            let corner = Node::at(x, y, node.z);
+            if !crossing(self[corner]) && (open(x, node.y) || open(node.x, y)) {
+                moves.push(corner);
+            }
+        }
+        moves
+    }
+
+    // Passing under a bridge covers two tiles in one move
+    pub fn move_cost(&self, from: Node, to: Node) -> f32 {
+        if self[from] == Tile::Tunnel && self[to] == Tile::Tunnel {
+            2.0
+        } else if self.topology == Topology::Square
+            && from.z == to.z
+            && from.x != to.x
+            && from.y != to.y
+        {
+            std::f32::consts::SQRT_2
+        } else {
+            1.0
+        }
+    }
+
+    // Summed move costs along a path; more than its hop count past tunnels and diagonals
+    pub fn path_cost(&self, path: &[Node]) -> f32 {
+        path.windows(2).map(|p| self.move_cost(p[0], p[1])).sum()
+    }
+
+    // Same-floor neighbor in direction `dir`
+    pub fn neighbor(&self, node: Node, dir: usize) -> Option<Node> {
+        let (nx, ny) = self.topology.step(node, dir);
+        self.inside(nx, ny)
+            .then(|| Node::at(nx as usize, ny as usize, node.z))
+    }
+
+    // Lattice cells two steps away, paired with the wall tile between them.
+    // The same cell on the floor above/below links directly (its own "wall")
+    pub fn cell_links(&self, node: Node) -> Vec<(Node, Node)> {
+        let mut links: Vec<(Node, Node)> = Vec::new();
+        for dir in 0..self.topology.direction_count() {
+            if let Some(wall) = self.neighbor(node, dir)
+                && let Some(cell) = self.neighbor(wall, dir)
+                && !links.contains(&(cell, wall))
+            {
+                links.push((cell, wall));
+            }
+        }
+
+        if node.z + 1 < self.floors {
+            let up = Node::at(node.x, node.y, node.z + 1);
+            links.push((up, up));
+        }
+        if node.z > 0 {
+            let down = Node::at(node.x, node.y, node.z - 1);
+            links.push((down, down));
         }
+        links
+    }
+
+    // Carve the passage between two linked cells: open the wall on the same floor,
+    // or build a staircase between floors
+    pub fn open_link(&mut self, a: Node, b: Node, wall: Node) {
+        if a.z == b.z {
+            self[wall] = Tile::Path;
+            return;
+        }
+
+        let lower = if a.z < b.z { a } else { b };
+        self.stairs.insert(lower);
+        for end in [a, b] {
+            if matches!(self[end], Tile::Path | Tile::Wall) {
+                self[end] = Tile::Stairs;
+            }
+        }
+    }
+
+    // Nearest lattice cell on the same floor, the cells that generators carve on
+    pub fn lattice_cell(&self, node: Node) -> Node {
+        if self.topology == Topology::Square {
+            let snap = |v: usize, size: usize| {
+                let v = v | 1;
+                if v >= size - 1 { v - 2 } else { v }
+            };
+            return Node::at(snap(node.x, self.width), snap(node.y, self.height), node.z);
+        }
+
+        let target = self.local_center(node);
+        let mut best = None;
+        let mut best_dist = f32::INFINITY;
+        for dy in -2..=2isize {
+            for dx in -2..=2isize {
+                let (x, y) = (node.x as isize + dx, node.y as isize + dy);
+                if !self.inside(x, y) {
+                    continue;
+                }
+                let cell = Node::at(x as usize, y as usize, node.z);
+                let dist = self.local_center(cell).distance(target);
+                if self.topology.is_cell(cell) && dist < best_dist {
+                    best = Some(cell);
+                    best_dist = dist;
+                }
+            }
+        }
+        best.unwrap_or(node)
+    }
+
+    // Open the tiles joining an off-lattice start/goal to the carved maze
+    pub fn connect_to_lattice(&mut self, node: Node) {
+        let cell = self.lattice_cell(node);
+        let target = self.local_center(cell);
+        let mut current = node;
+
+        // The lattice is at most a couple of tiles away, so a short greedy walk suffices
+        for _ in 0..8 {
+            if current == cell {
+                break;
+            }
+            let Some(next) = (0..self.topology.direction_count())
+                .filter_map(|dir| self.neighbor(current, dir))
+                .min_by(|a, b| {
+                    let da = self.local_center(*a).distance(target);
+                    let db = self.local_center(*b).distance(target);
+                    da.total_cmp(&db)
+                })
+            else {
+                break;
+            };
+            if self[next] == Tile::Wall {
+                self[next] = Tile::Path;
+            }
+            current = next;
+        }
+    }
+
+    // Position within its own floor in tile units, independent of the view
+    fn local_center(&self, node: Node) -> Vec2 {
+        self.topology.center(node.x as f32, node.y as f32, 1.0)
+    }
+
+    pub fn visible(&self, z: usize) -> bool {
+        match self.view {
+            FloorView::Single(floor) => floor == z,
+            FloorView::All => z < self.floors,
+        }
+    }
+
+    // Size of the whole drawing; in the all-floors view the floors share this width
+    pub fn pixel_size(&self, cell_size: f32) -> Vec2 {
+        vec2(
+            self.topology.pixel_width(self.width, cell_size),
+            self.topology.pixel_height(self.height, cell_size),
+        )
+    }
+
+    // Tile size on screen; floors shrink to share the width in the all-floors view
+    pub fn view_cell_size(&self, cell_size: f32) -> f32 {
+        match self.view {
+            FloorView::Single(_) => cell_size,
+            FloorView::All => cell_size / self.floors as f32,
+        }
+    }
+
+    fn floor_origin(&self, z: usize, cell_size: f32) -> Vec2 {
+        match self.view {
+            FloorView::Single(_) => Vec2::ZERO,
+            FloorView::All => {
+                let panel = self
+                    .topology
+                    .pixel_width(self.width, self.view_cell_size(cell_size));
+                vec2(z as f32 * panel, 0.0)
+            }
+        }
+    }
+
+    pub fn center(&self, node: Node, cell_size: f32) -> Vec2 {
+        self.project(node.position(), cell_size)
+    }
+
+    // Screen position of a (possibly fractional) tile position; the floor is rounded
+    pub fn project(&self, pos: Vec3, cell_size: f32) -> Vec2 {
+        let z = pos.z.round().max(0.0) as usize;
+        self.floor_origin(z, cell_size)
+            + self
+                .topology
+                .center(pos.x, pos.y, self.view_cell_size(cell_size))
+    }
+
+    pub fn tile_at(&self, pos: Vec2, cell_size: f32) -> Option<Node> {
+        let z = match self.view {
+            FloorView::Single(floor) => floor,
+            FloorView::All => {
+                let panel = self
+                    .topology
+                    .pixel_width(self.width, self.view_cell_size(cell_size));
+                (pos.x / panel).floor().max(0.0) as usize
+            }
+        };
+        if z >= self.floors {
+            return None;
+        }
+
+        let local = pos - self.floor_origin(z, cell_size);
+        let (x, y) = self.topology.tile_at(local, self.view_cell_size(cell_size));
+        self.inside(x, y)
+            .then(|| Node::at(x as usize, y as usize, z))
+    }
+
+    pub fn draw_tile(&self, node: Node, cell_size: f32, color: Color) {
+        if let Topology::Polar { .. } = self.topology {
+            if self.visible(node.z) {
+                self.topology.draw_polar_tile(
+                    node,
+                    self.floor_origin(node.z, cell_size),
+                    self.view_cell_size(cell_size),
+                    color,
+                );
+            }
+        } else if self.visible(node.z) {
+            self.topology.draw_tile(
+                self.center(node, cell_size),
+                self.view_cell_size(cell_size),
+                color,
+            );
+        }
+    }
+
+    pub fn draw_tile_lines(&self, node: Node, cell_size: f32, thickness: f32, color: Color) {
+        if let Topology::Polar { .. } = self.topology {
+            if self.visible(node.z) {
+                self.topology.draw_polar_tile_lines(
+                    node,
+                    self.floor_origin(node.z, cell_size),
+                    self.view_cell_size(cell_size),
+                    thickness,
+                    color,
+                );
+            }
+        } else if self.visible(node.z) {
+            self.topology.draw_tile_lines(
+                self.center(node, cell_size),
+                self.view_cell_size(cell_size),
+                thickness,
+                color,
+            );
+        }
+    }
+
+    // Plain-text format: '#' wall, '.' path, 'S' start, 'G' goal (the first one is primary),
+    // '=' stairs, '+' bridge, '~' tunnel, preceded by a "hex" or "polar" line for those
+    // layouts. Floors are separated by "---" lines and followed by one "stair x y z" line
+    // per staircase
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_text(&fs::read_to_string(path)?)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::with_capacity((self.width + 1) * self.height * self.floors);
+        match self.topology {
+            Topology::Square => {}
+            Topology::Hex => text.push_str("hex\n"),
+            Topology::Polar { .. } => text.push_str("polar\n"),
+        }
+        for (z, layer) in self.layers.iter().enumerate() {
+            if z > 0 {
+                text.push_str("---\n");
+            }
+            for row in layer {
+                for tile in row {
+                    text.push(match tile {
+                        Tile::Wall => '#',
+                        Tile::Path => '.',
+                        Tile::Start => 'S',
+                        Tile::Goal => 'G',
+                        Tile::Stairs => '=',
+                        Tile::Bridge => '+',
+                        Tile::Tunnel => '~',
+                    });
+                }
+                text.push('\n');
+            }
+        }
+
+        let mut stairs: Vec<&Node> = self.stairs.iter().collect();
+        stairs.sort_by_key(|n| (n.z, n.y, n.x));
+        for stair in stairs {
+            text.push_str(&format!("stair {} {} {}\n", stair.x, stair.y, stair.z));
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+        let mut rows: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+
+        // Optional header line naming the topology; polar rings follow from the size
+        let header = rows.first().copied();
+        if matches!(header, Some("hex" | "polar")) {
+            rows.remove(0);
+        }
+
+        let mut stairs = Vec::new();
+        let mut layers: Vec<Vec<&str>> = vec![Vec::new()];
+        for row in rows {
+            if let Some(coords) = row.strip_prefix("stair ") {
+                let v: Vec<usize> = coords
+                    .split_whitespace()
+                    .filter_map(|c| c.parse().ok())
+                    .collect();
+                let [x, y, z] = v[..] else {
+                    return Err(invalid("malformed stair line"));
+                };
+                stairs.push(Node::at(x, y, z));
+            } else if row == "---" {
+                layers.push(Vec::new());
+            } else if let Some(layer) = layers.last_mut() {
+                layer.push(row);
+            }
+        }
+
+        let height = layers[0].len();
+        let width = layers
+            .iter()
+            .flatten()
+            .map(|r| r.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        if width < 3 || height < 3 {
+            return Err(invalid("maze file must be at least 3x3"));
+        }
+        if layers.iter().any(|l| l.len() != height) {
+            return Err(invalid("all floors must have the same height"));
+        }
+
+        let topology = match header {
+            Some("hex") => Topology::Hex,
+            Some("polar") => Topology::polar(width, height),
+            _ => Topology::Square,
+        };
+        let mut maze = Maze::with_floors(width, height, layers.len(), topology);
+        let mut start = None;
+        let mut goals = Vec::new();
+
+        for (z, layer) in layers.iter().enumerate() {
+            for (y, row) in layer.iter().enumerate() {
+                for (x, c) in row.chars().enumerate() {
+                    let node = Node::at(x, y, z);
+                    maze[node] = match c {
+                        '.' | ' ' => Tile::Path,
+                        '=' => Tile::Stairs,
+                        '+' => Tile::Bridge,
+                        '~' => Tile::Tunnel,
+                        'S' => {
+                            start = Some(node);
+                            Tile::Start
+                        }
+                        'G' => {
+                            goals.push(node);
+                            Tile::Goal
+                        }
+                        _ => Tile::Wall,
+                    };
+                }
+            }
+        }
+
+        for stair in stairs {
+            if stair.z + 1 < maze.floors && maze.contains(stair) {
+                maze.stairs.insert(stair);
+            }
+        }
+
+        // Files without markers fall back to the default corners
+        maze.set_start(start.unwrap_or(maze.start));
+        maze.set_goal(goals.first().copied().unwrap_or(maze.goal));
+        for &goal in goals.iter().skip(1) {
+            maze.add_extra_goal(goal);
+        }
+        Ok(maze)
+    }
+
+    // Random walkable cell, falling back to the start
+    pub fn random_open_cell(&self, attempts: usize) -> Node {
+        let mut rng = crate::rng::rng();
+        use ::rand::Rng;
+        for _ in 0..attempts {
+            let x = rng.random_range(1..self.width - 1);
+            let y = rng.random_range(1..self.height - 1);
+            let z = rng.random_range(0..self.floors);
+            let node = Node::at(x, y, z);
+            if self[node] == Tile::Path {
+                return node;
+            }
+        }
+        self.start
     }
 
     pub fn in_bounds(x: isize, y: isize, w: usize, h: usize) -> bool {
         x > 0 && x < (w as isize - 1) && y > 0 && y < (h as isize - 1)
     }
 
-    pub fn draw(&self, cell_size: f32) {
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let color = match self.grid[y][x] {
-                    Tile::Wall => PURPLE,
-                    Tile::Path => BLACK,
-                    Tile::Start => GREEN,
-                    Tile::Goal => RED,
-                };
+    // Inside the border, the layout and the mask, if there is one
+    pub fn inside(&self, x: isize, y: isize) -> bool {
+        Maze::in_bounds(x, y, self.width, self.height)
+            && self.topology.contains(x, y)
+            && self
+                .mask
+                .as_ref()
+                .is_none_or(|mask| mask.contains(x as usize, y as usize, self.width, self.height))
+    }
 
-                draw_rectangle(
-                    x as f32 * cell_size,
-                    y as f32 * cell_size,
-                    cell_size,
-                    cell_size,
-                    color,
+    // Masked-out tiles bordering the shape are still drawn, as its outline
+    pub fn visible_tile(&self, node: Node) -> bool {
+        let (x, y) = (node.x as isize, node.y as isize);
+        if !self.topology.contains(x, y) {
+            return false;
+        }
+        self.mask.is_none()
+            || self.inside(node.x as isize, node.y as isize)
+            || (0..self.topology.direction_count()).any(|dir| {
+                let (x, y) = self.topology.step(node, dir);
+                self.inside(x, y)
+            })
+    }
+
+    // Move endpoints that fall outside the mask onto carvable cells, keeping the goals
+    // in the same region as the start
+    pub fn fit_endpoints_to_mask(&mut self) {
+        if self.mask.is_none() {
+            return;
+        }
+        let (width, height) = (self.width, self.height);
+        let cells: Vec<Node> = (0..self.floors)
+            .flat_map(|z| (0..height).flat_map(move |y| (0..width).map(move |x| Node::at(x, y, z))))
+            .filter(|&n| self.topology.is_cell(n) && self.contains(n))
+            .collect();
+        let nearest = |target: Node, pool: &[Node]| {
+            pool.iter().copied().min_by(|a, b| {
+                let da = a.position().distance(target.position());
+                let db = b.position().distance(target.position());
+                da.total_cmp(&db)
+            })
+        };
+        let usable = |maze: &Maze, n: Node, pool: &[Node]| {
+            maze.contains(n) && pool.contains(&maze.lattice_cell(n))
+        };
+
+        if !usable(self, self.start, &cells)
+            && let Some(start) = nearest(self.start, &cells)
+        {
+            self.start = start;
+        }
+
+        // Lattice cells reachable from the start through the mask
+        let mut region = vec![self.lattice_cell(self.start)];
+        let mut seen: HashSet<Node> = region.iter().copied().collect();
+        let mut i = 0;
+        while i < region.len() {
+            for (cell, _) in self.cell_links(region[i]) {
+                if seen.insert(cell) {
+                    region.push(cell);
+                }
+            }
+            i += 1;
+        }
+
+        let mut goals = Vec::new();
+        for goal in self.goals() {
+            let goal = if usable(self, goal, &region) {
+                Some(goal)
+            } else {
+                nearest(goal, &region)
+            };
+            if let Some(goal) = goal
+                && goal != self.start
+                && !goals.contains(&goal)
+            {
+                goals.push(goal);
+            }
+        }
+        if let Some((&first, rest)) = goals.split_first() {
+            self.goal = first;
+            self.extra_goals = rest.to_vec();
+        }
+    }
+
+    // Only tiles around `area` (in the same pixel space) are drawn, which keeps huge
+    // mazes cheap when zoomed in
+    pub fn draw(&self, cell_size: f32, area: Rect) {
+        let margin = self.view_cell_size(cell_size);
+        let area = Rect::new(
+            area.x - margin,
+            area.y - margin,
+            area.w + 2.0 * margin,
+            area.h + 2.0 * margin,
+        );
+        let polar = matches!(self.topology, Topology::Polar { .. });
+
+        for z in (0..self.floors).filter(|&z| self.visible(z)) {
+            // The hub inside the innermost ring is solid
+            if let Topology::Polar { .. } = self.topology {
+                let size = self.view_cell_size(cell_size);
+                let middle = self.topology.pixel_width(self.width, size) / 2.0;
+                let hub = self.floor_origin(z, cell_size) + vec2(middle, middle);
+                draw_circle(hub.x, hub.y, 2.5 * size, PURPLE);
+            }
+            for y in 0..self.height {
+                // Square and hex rows share one screen y, so whole rows can be skipped
+                let row_y = self.center(Node::at(0, y, z), cell_size).y;
+                if !polar && (row_y < area.y || row_y > area.bottom()) {
+                    continue;
+                }
+                for x in 0..self.width {
+                    let node = Node::at(x, y, z);
+                    if !self.visible_tile(node) || !area.contains(self.center(node, cell_size)) {
+                        continue;
+                    }
+                    self.draw_tile(node, cell_size, self[node].color());
+                    if self[node] == Tile::Bridge {
+                        self.draw_bridge_rails(node, cell_size);
+                    }
+                }
+            }
+
+            if self.floors > 1 {
+                let corner = self.floor_origin(z, cell_size);
+                draw_text(
+                    format!("Floor {}", z + 1),
+                    corner.x + 4.0,
+                    corner.y + 16.0,
+                    20.0,
+                    WHITE,
                 );
             }
         }
     }
+
+    // Railings along the deck, parallel to the passage that runs over the tunnel
+    fn draw_bridge_rails(&self, node: Node, cell_size: f32) {
+        let c = self.center(node, cell_size);
+        let h = self.view_cell_size(cell_size) / 2.0;
+        let under_east_west = (0..self.topology.direction_count()).any(|dir| {
+            self.neighbor(node, dir)
+                .is_some_and(|n| self[n] == Tile::Tunnel && n.y == node.y)
+        });
+
+        if under_east_west {
+            draw_line(c.x - h, c.y - h, c.x - h, c.y + h, 2.0, LIGHTGRAY);
+            draw_line(c.x + h, c.y - h, c.x + h, c.y + h, 2.0, LIGHTGRAY);
+        } else {
+            draw_line(c.x - h, c.y - h, c.x + h, c.y - h, 2.0, LIGHTGRAY);
+            draw_line(c.x - h, c.y + h, c.x + h, c.y + h, 2.0, LIGHTGRAY);
+        }
+    }
 }
@@ -2,14 +2,25 @@
 #![allow(unused_imports)]
 #![allow(unused_variables)]
 
+use macroquad::prelude::{Vec3, vec3};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Node {
     pub x: usize,
     pub y: usize,
+    pub z: usize, // Floor
 }
 
 impl Node {
     pub fn new(x: usize, y: usize) -> Self {
-        Node { x, y}
+        Node { x, y, z: 0 }
+    }
+
+    pub fn at(x: usize, y: usize, z: usize) -> Self {
+        Node { x, y, z }
     }
-}
\ No newline at end of file
+
+    pub fn position(self) -> Vec3 {
+        vec3(self.x as f32, self.y as f32, self.z as f32)
+    }
+}
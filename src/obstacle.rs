@@ -1,6 +1,8 @@
 use crate::config::AppConfig;
+use crate::constants::OBSTACLE_INTERVAL_SEC;
 use crate::maze::{Maze, Tile};
 use crate::node::Node;
+use crate::topology::Topology;
 use macroquad::prelude::*;
 
 #[derive(Clone, Debug)]
@@ -8,24 +10,23 @@ pub struct DynamicObstacle {
     pub position: Node,
     pub color: Color,
     // Movement logic can be expanded. For now, let's say they toggle or move linearly.
-    pub move_dir: (isize, isize),
+    pub move_dir: usize, // Direction index into the maze topology
     pub move_timer: f64,
     pub move_interval: f64,
 }
 
 impl DynamicObstacle {
-    pub fn new(start: Node) -> Self {
-        let mut rng = ::rand::rng();
+    pub fn new(start: Node, topology: Topology) -> Self {
+        let mut rng = crate::rng::rng();
         use ::rand::Rng;
-        let dirs = [(0, 1), (0, -1), (1, 0), (-1, 0)];
-        let dir = dirs[rng.random_range(0..4)];
+        let dir = rng.random_range(0..topology.direction_count());
 
         Self {
             position: start,
             color: ORANGE,
             move_dir: dir,
             move_timer: 0.0,
-            move_interval: 1.0, // Move every 1 second
+            move_interval: OBSTACLE_INTERVAL_SEC,
         }
     }
 
@@ -34,46 +35,30 @@ impl DynamicObstacle {
         if self.move_timer >= self.move_interval {
             self.move_timer = 0.0;
 
-            let nx = self.position.x as isize + self.move_dir.0;
-            let ny = self.position.y as isize + self.move_dir.1;
+            let count = maze.topology.direction_count();
+            let next = maze
+                .neighbor(self.position, self.move_dir % count)
+                .filter(|n| maze[*n] == Tile::Path);
 
-            if Maze::in_bounds(nx, ny, maze.width, maze.height)
-                && maze.grid[ny as usize][nx as usize] == Tile::Path
-            {
-                self.position.x = nx as usize;
-                self.position.y = ny as usize;
+            if let Some(next) = next {
+                self.position = next;
             } else {
                 // Bounce
-                self.move_dir.0 *= -1;
-                self.move_dir.1 *= -1;
+                self.move_dir = (self.move_dir + count / 2) % count;
             }
         }
     }
 
-    pub fn draw(&self, cell_size: f32) {
-        draw_rectangle(
-            self.position.x as f32 * cell_size,
-            self.position.y as f32 * cell_size,
-            cell_size,
-            cell_size,
-            self.color,
-        );
+    pub fn draw(&self, maze: &Maze, cell_size: f32) {
+        if !maze.visible(self.position.z) {
+            return;
+        }
+        maze.draw_tile(self.position, cell_size, self.color);
+
         // Draw an X or specific marking
-        draw_line(
-            self.position.x as f32 * cell_size,
-            self.position.y as f32 * cell_size,
-            (self.position.x as f32 + 1.0) * cell_size,
-            (self.position.y as f32 + 1.0) * cell_size,
-            2.0,
-            BLACK,
-        );
-        draw_line(
-            (self.position.x as f32 + 1.0) * cell_size,
-            self.position.y as f32 * cell_size,
-            self.position.x as f32 * cell_size,
-            (self.position.y as f32 + 1.0) * cell_size,
-            2.0,
-            BLACK,
-        );
+        let c = maze.center(self.position, cell_size);
+        let h = maze.view_cell_size(cell_size) / 2.0;
+        draw_line(c.x - h, c.y - h, c.x + h, c.y + h, 2.0, BLACK);
+        draw_line(c.x + h, c.y - h, c.x - h, c.y + h, 2.0, BLACK);
     }
 }
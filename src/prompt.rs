@@ -0,0 +1,79 @@
+use macroquad::prelude::*;
+
+use crate::expr::{FUNCTIONS, VARIABLES};
+use crate::input::FrameInput;
+
+const HEIGHT: f32 = 52.0;
+const MAX_LENGTH: usize = 120;
+const PROMPT_COLOR: Color = Color::new(0.05, 0.05, 0.1, 0.92);
+
+// One-line text box along the bottom of the maze for a heuristic formula. It reads the
+// typed text from `FrameInput`, so a replayed session types the same formula
+#[derive(Default)]
+pub struct Prompt {
+    pub open: bool,
+    pub text: String,
+    pub error: Option<String>,
+}
+
+impl Prompt {
+    pub fn show(&mut self, text: &str) {
+        self.open = true;
+        self.text = text.to_string();
+        self.error = None;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.error = None;
+    }
+
+    // The text once Enter is pressed; Escape closes without it
+    pub fn update(&mut self, input: &FrameInput) -> Option<String> {
+        if input.key_pressed(KeyCode::Escape) {
+            self.close();
+            return None;
+        }
+        if input.key_pressed(KeyCode::Backspace) {
+            self.text.pop();
+        }
+        for c in input.text.chars() {
+            if self.text.len() < MAX_LENGTH {
+                self.text.push(c);
+            }
+        }
+        if !input.text.is_empty() || input.key_pressed(KeyCode::Backspace) {
+            self.error = None;
+        }
+        input
+            .key_pressed(KeyCode::Enter)
+            .then(|| self.text.trim().to_string())
+    }
+
+    pub fn draw(&self, area: Rect) {
+        if !self.open {
+            return;
+        }
+        let y = area.y + area.h - HEIGHT;
+        draw_rectangle(area.x, y, area.w, HEIGHT, PROMPT_COLOR);
+        draw_text(
+            format!("h = {}_", self.text),
+            area.x + 10.0,
+            y + 22.0,
+            24.0,
+            WHITE,
+        );
+        let (hint, color) = match &self.error {
+            Some(error) => (error.clone(), RED),
+            None => (
+                format!(
+                    "{} | {} | + - * / ^ | [Enter] Use [Esc] Cancel",
+                    VARIABLES.join(" "),
+                    FUNCTIONS.join(" "),
+                ),
+                LIGHTGRAY,
+            ),
+        };
+        draw_text(&hint, area.x + 10.0, y + 44.0, 18.0, color);
+    }
+}
@@ -0,0 +1,165 @@
+use macroquad::prelude::*;
+
+use crate::algorithm::{AStarVisualizer, ScoreOverlay};
+use crate::heuristic::Heuristics;
+use crate::maze::Maze;
+use crate::node::Node;
+
+const LABEL_HEIGHT: f32 = 20.0;
+
+// One heuristic's run on its own copy of the maze
+pub struct Lane {
+    pub heuristic_index: usize,
+    pub name: String,
+    pub maze: Maze,
+    pub solver: AStarVisualizer,
+    pub admissible: bool, // Whether the w bound holds for this lane's heuristic
+}
+
+impl Lane {
+    pub fn cost(&self) -> Option<f32> {
+        self.solver.cost()
+    }
+}
+
+// Side-by-side comparison: every lane searches the same maze in lockstep
+pub struct Race {
+    pub lanes: Vec<Lane>,
+    pub optimal: Option<f32>, // Cheapest cost to any goal, from a plain Dijkstra run
+}
+
+impl Race {
+    pub fn new(
+        maze: &Maze,
+        goals: &[Node],
+        heuristics: &Heuristics,
+        lanes: &[usize],
+        weight: f32,
+    ) -> Self {
+        let lanes = lanes
+            .iter()
+            .map(|&heuristic_index| {
+                let mut solver = AStarVisualizer::with_goals(maze, goals.to_vec());
+                solver.weight = weight;
+                let heuristic = heuristics.get(heuristic_index);
+                Lane {
+                    heuristic_index,
+                    name: heuristic.name().to_string(),
+                    maze: maze.clone(),
+                    solver,
+                    admissible: heuristic.admissible(maze),
+                }
+            })
+            .collect();
+        let optimal = AStarVisualizer::optimal_cost(maze, goals);
+
+        Self { lanes, optimal }
+    }
+
+    pub fn done(&self) -> bool {
+        self.lanes
+            .iter()
+            .all(|lane| lane.solver.found || lane.solver.open.is_empty())
+    }
+
+    pub fn step(&mut self, steps: usize, heuristics: &Heuristics) {
+        for lane in &mut self.lanes {
+            let heuristic = heuristics.get(lane.heuristic_index);
+            for _ in 0..steps {
+                lane.solver.step(&lane.maze, heuristic);
+            }
+        }
+    }
+
+    // Lays the panels out in the grid that gives them the largest scale
+    fn panels(&self, area: Rect, maze_size: Vec2) -> (Vec<Rect>, f32) {
+        let n = self.lanes.len().max(1);
+        let (cols, scale) = (1..=n)
+            .map(|cols| {
+                let rows = n.div_ceil(cols);
+                let w = area.w / cols as f32;
+                let h = area.h / rows as f32 - LABEL_HEIGHT;
+                (cols, (w / maze_size.x).min(h / maze_size.y))
+            })
+            .fold((1, 0.0), |best, c| if c.1 > best.1 { c } else { best });
+
+        let rows = n.div_ceil(cols);
+        let (w, h) = (area.w / cols as f32, area.h / rows as f32);
+        let rects = (0..n)
+            .map(|i| {
+                Rect::new(
+                    area.x + (i % cols) as f32 * w,
+                    area.y + (i / cols) as f32 * h,
+                    w,
+                    h,
+                )
+            })
+            .collect();
+        (rects, scale)
+    }
+
+    pub fn draw(&self, area: Rect, cell_size: f32, overlay: ScoreOverlay) {
+        let Some(first) = self.lanes.first() else {
+            return;
+        };
+        let maze_size = vec2(
+            first.maze.topology.pixel_width(first.maze.width, cell_size),
+            first
+                .maze
+                .topology
+                .pixel_height(first.maze.height, cell_size),
+        );
+        let (panels, scale) = self.panels(area, maze_size);
+
+        for (lane, panel) in self.lanes.iter().zip(panels) {
+            // Map the maze's own pixel space into the panel, under its label
+            let origin = vec2(panel.x, panel.y + LABEL_HEIGHT);
+            set_camera(&Camera2D::from_display_rect(Rect::new(
+                -origin.x / scale,
+                -origin.y / scale,
+                screen_width() / scale,
+                screen_height() / scale,
+            )));
+            lane.maze
+                .draw(cell_size, Rect::new(0.0, 0.0, maze_size.x, maze_size.y));
+            lane.solver.draw(&lane.maze, cell_size, overlay, scale);
+            set_default_camera();
+
+            self.draw_label(lane, panel);
+        }
+    }
+
+    fn draw_label(&self, lane: &Lane, panel: Rect) {
+        let solver = &lane.solver;
+        let (verdict, color) = match (lane.cost(), self.optimal) {
+            (Some(cost), Some(best)) if cost <= best + 1e-3 => ("optimal".to_string(), GREEN),
+            // A weight w promises at most w times the optimal cost for admissible heuristics
+            (Some(cost), Some(best)) if !lane.admissible || cost <= best * solver.weight + 1e-3 => {
+                (format!("+{:.1} over optimal", cost - best), ORANGE)
+            }
+            (Some(cost), Some(best)) => (format!("+{:.1}, past the w bound", cost - best), RED),
+            (Some(_), None) => (String::new(), WHITE),
+            (None, _) if solver.open.is_empty() => ("no path".to_string(), RED),
+            (None, _) => ("searching".to_string(), LIGHTGRAY),
+        };
+        let path_cost = solver.path.as_ref().map_or(0.0, |p| lane.maze.path_cost(p));
+
+        draw_rectangle(
+            panel.x,
+            panel.y,
+            panel.w,
+            LABEL_HEIGHT,
+            Color::new(0.05, 0.05, 0.1, 1.0),
+        );
+        draw_text(
+            format!(
+                "{} | Exp: {} | Cost: {:.1} | {}",
+                lane.name, solver.cursor, path_cost, verdict
+            ),
+            panel.x + 4.0,
+            panel.y + 15.0,
+            18.0,
+            color,
+        );
+    }
+}
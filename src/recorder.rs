@@ -0,0 +1,212 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+
+use crate::algorithm::AStarVisualizer;
+use crate::config::AppConfig;
+use crate::constants::{RECORD_CELL_SIZE, RECORD_FRAME_DELAY, RECORD_GIF_SPEED, RECORD_HOLD_DELAY};
+use crate::export::{Canvas, Figure};
+use crate::generator::MazeVisualizer;
+use crate::heuristic::Heuristics;
+use crate::maze::Maze;
+
+// Where the frames go: one animated GIF, or numbered PNGs in a directory
+enum Sink {
+    Gif(GifEncoder<BufWriter<File>>),
+    Frames { dir: PathBuf, count: usize },
+}
+
+// Captures every `every`-th step of a generator or solver, straight from the model,
+// so no frames are lost no matter how many steps a window frame would batch
+pub struct Recorder {
+    every: usize,
+    steps: usize,
+    path: PathBuf,
+    sink: Option<Sink>, // Opened with the first frame, once its size is known
+}
+
+impl Recorder {
+    // A path ending in ".gif" records an animation, anything else a frame directory
+    pub fn new(path: impl AsRef<Path>, every: usize) -> Self {
+        Self {
+            every: every.max(1),
+            steps: 0,
+            path: path.as_ref().to_path_buf(),
+            sink: None,
+        }
+    }
+
+    // Call after every step; only every n-th one is captured
+    pub fn step(&mut self, figure: impl FnOnce() -> Figure) -> io::Result<()> {
+        self.steps += 1;
+        if self.steps.is_multiple_of(self.every) {
+            self.capture(&figure(), RECORD_FRAME_DELAY)?;
+        }
+        Ok(())
+    }
+
+    pub fn capture(&mut self, figure: &Figure, delay: u16) -> io::Result<()> {
+        let canvas = figure.rasterize();
+        if self.sink.is_none() {
+            self.sink = Some(self.open(&canvas)?);
+        }
+
+        match self.sink.as_mut() {
+            Some(Sink::Gif(gif)) => {
+                let pixels = canvas
+                    .rgb()
+                    .into_iter()
+                    .flat_map(|[r, g, b]| [r, g, b, 255]);
+                let buffer = RgbaImage::from_raw(
+                    canvas.width as u32,
+                    canvas.height as u32,
+                    pixels.collect(),
+                )
+                .expect("canvas size matches its pixels");
+                // `delay` is in hundredths of a second
+                let delay = Delay::from_numer_denom_ms(delay as u32 * 10, 1);
+                gif.encode_frame(Frame::from_parts(buffer, 0, 0, delay))
+                    .map_err(gif_error)
+            }
+            Some(Sink::Frames { dir, count }) => {
+                *count += 1;
+                canvas.save_png(dir.join(format!("frame_{:05}.png", count)))
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn open(&self, canvas: &Canvas) -> io::Result<Sink> {
+        let is_gif = self
+            .path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+
+        if is_gif {
+            if canvas.width > u16::MAX as usize || canvas.height > u16::MAX as usize {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "GIF frames must be at most 65535 pixels on each side",
+                ));
+            }
+            let file = BufWriter::new(File::create(&self.path)?);
+            let mut gif = GifEncoder::new_with_speed(file, RECORD_GIF_SPEED);
+            gif.set_repeat(Repeat::Infinite).map_err(gif_error)?;
+            Ok(Sink::Gif(gif))
+        } else {
+            fs::create_dir_all(&self.path)?;
+            Ok(Sink::Frames {
+                dir: self.path.clone(),
+                count: 0,
+            })
+        }
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        // The GIF encoder writes the trailer when it's dropped
+        drop(self.sink);
+        Ok(())
+    }
+}
+
+fn gif_error(err: image::ImageError) -> io::Error {
+    io::Error::other(format!("GIF: {}", err))
+}
+
+// Generate a maze and solve it without opening a window, recording both phases
+pub fn record_session(config: &AppConfig, path: impl AsRef<Path>, every: usize) -> io::Result<()> {
+    let mut recorder = Recorder::new(path, every);
+    let mut maze = Maze::with_floors(
+        config.grid_width,
+        config.grid_height,
+        config.floors,
+        config.topology,
+    );
+
+    let snapshot = |maze: &Maze, solver: Option<&AStarVisualizer>| {
+        Figure::capture(maze, solver, &[], &[], RECORD_CELL_SIZE)
+    };
+
+    let mut generator =
+        MazeVisualizer::new(&mut maze, config.algorithm, config.weave, config.density);
+    recorder.capture(&snapshot(&maze, None), RECORD_FRAME_DELAY)?;
+    while !generator.done {
+        generator.step(&mut maze);
+        recorder.step(|| snapshot(&maze, None))?;
+    }
+    recorder.capture(&snapshot(&maze, None), RECORD_HOLD_DELAY)?;
+
+    let mut heuristics = Heuristics::builtin();
+    heuristics.prepare(config.heuristic, &maze, config);
+    let heuristic = heuristics.get(config.heuristic);
+    let mut solver = AStarVisualizer::new(&maze);
+    solver.weight = config.heuristic_weight;
+    while !solver.found && !solver.open.is_empty() {
+        solver.step(&maze, heuristic);
+        recorder.step(|| snapshot(&maze, Some(&solver)))?;
+    }
+    recorder.capture(&snapshot(&maze, Some(&solver)), RECORD_HOLD_DELAY)?;
+
+    recorder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::AnimationDecoder;
+    use image::codecs::gif::GifDecoder;
+
+    #[test]
+    fn gif_frames_decode_to_the_captured_pixels() {
+        let config = AppConfig {
+            grid_width: 9,
+            grid_height: 7,
+            ..Default::default()
+        };
+        let mut maze = Maze::with_floors(
+            config.grid_width,
+            config.grid_height,
+            config.floors,
+            config.topology,
+        );
+        let path = std::env::temp_dir().join(format!("maze-recorder-{}.gif", std::process::id()));
+        let mut recorder = Recorder::new(&path, 3);
+        let mut figures = Vec::new();
+        let mut generator =
+            MazeVisualizer::new(&mut maze, config.algorithm, config.weave, config.density);
+        while !generator.done {
+            generator.step(&mut maze);
+            recorder
+                .step(|| {
+                    let figure = Figure::capture(&maze, None, &[], &[], RECORD_CELL_SIZE);
+                    figures.push(figure.rasterize());
+                    figure
+                })
+                .unwrap();
+        }
+        let last = Figure::capture(&maze, None, &[], &[], RECORD_CELL_SIZE);
+        recorder.capture(&last, RECORD_HOLD_DELAY).unwrap();
+        figures.push(last.rasterize());
+        recorder.finish().unwrap();
+
+        let decoder = GifDecoder::new(io::BufReader::new(File::open(&path).unwrap())).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+        assert!(frames.len() > 1);
+        assert_eq!(frames.len(), figures.len());
+        for (frame, canvas) in frames.iter().zip(&figures) {
+            let buffer = frame.buffer();
+            assert_eq!(
+                (buffer.width() as usize, buffer.height() as usize),
+                (canvas.width, canvas.height)
+            );
+            let pixels: Vec<[u8; 3]> = buffer.pixels().map(|p| [p[0], p[1], p[2]]).collect();
+            assert_eq!(pixels, canvas.rgb());
+        }
+        let (numer, denom) = frames.last().unwrap().delay().numer_denom_ms();
+        assert_eq!(numer / denom, RECORD_HOLD_DELAY as u32 * 10);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,41 @@
+use std::cell::{Cell, RefCell};
+
+use ::rand::rngs::StdRng;
+use ::rand::{RngCore, SeedableRng};
+
+// One seeded generator behind all the randomness (maze generation, obstacles, crowd
+// heuristics), so a session can be replayed exactly from its seed
+thread_local! {
+    static SEED: Cell<u64> = Cell::new(::rand::random());
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::seed_from_u64(seed()));
+}
+
+pub fn seed() -> u64 {
+    SEED.with(Cell::get)
+}
+
+pub fn reseed(seed: u64) {
+    SEED.with(|s| s.set(seed));
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+// Handle to the shared generator; use it wherever `rand::rng()` would go
+pub fn rng() -> SessionRng {
+    SessionRng
+}
+
+pub struct SessionRng;
+
+impl RngCore for SessionRng {
+    fn next_u32(&mut self) -> u32 {
+        RNG.with(|rng| rng.borrow_mut().next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        RNG.with(|rng| rng.borrow_mut().next_u64())
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        RNG.with(|rng| rng.borrow_mut().fill_bytes(dst))
+    }
+}
@@ -0,0 +1,384 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::{fs, io};
+
+use crate::agent::Agent;
+use crate::app_state::{AppState, GoalMode};
+use crate::maze::Maze;
+use crate::node::Node;
+use crate::obstacle::DynamicObstacle;
+
+const VERSION: usize = 1;
+
+// A whole situation in one JSON file: the maze (in the plain-text maze format, one string
+// per line), endpoints, agents with their heuristics, obstacles and the shared target.
+// Tiles are [x, y, floor]. Paths aren't stored; agents are routed again on load
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Scenario {
+    pub version: usize,
+    pub maze: Vec<String>,
+    pub start: [usize; 3],
+    pub goals: Vec<[usize; 3]>,
+    #[serde(default)]
+    pub goal_mode: Option<String>,
+    #[serde(default)]
+    pub global_target: Option<[usize; 3]>,
+    #[serde(default)]
+    pub agents: Vec<AgentEntry>,
+    #[serde(default)]
+    pub obstacles: Vec<ObstacleEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AgentEntry {
+    #[serde(default)]
+    pub main: bool,
+    pub position: [f32; 3],
+    #[serde(default)]
+    pub start: Option<[f32; 3]>,
+    #[serde(default)]
+    pub target: Option<[usize; 3]>,
+    #[serde(default)]
+    pub heuristic: usize,
+    #[serde(default)]
+    pub speed: Option<f32>,
+    #[serde(default)]
+    pub color: Option<[f32; 4]>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ObstacleEntry {
+    pub position: [usize; 3],
+    #[serde(default)]
+    pub direction: Option<usize>,
+    #[serde(default)]
+    pub interval: Option<f64>,
+}
+
+// Read first, so a file from another version is reported as such and not as a bad field
+#[derive(Deserialize)]
+struct Versioned {
+    version: usize,
+}
+
+impl Scenario {
+    pub fn capture(state: &AppState) -> Self {
+        let maze = &state.maze;
+        let agents = state
+            .agents
+            .iter()
+            .map(|agent| AgentEntry {
+                main: agent.is_main,
+                position: agent.position.to_array(),
+                start: Some(agent.start_position.to_array()),
+                target: agent.initial_target.map(tile),
+                heuristic: agent.heuristic_index,
+                speed: Some(agent.speed),
+                color: Some(agent.color.into()),
+            })
+            .collect();
+        let obstacles = state
+            .obstacles
+            .iter()
+            .map(|obstacle| ObstacleEntry {
+                position: tile(obstacle.position),
+                direction: Some(obstacle.move_dir),
+                interval: Some(obstacle.move_interval),
+            })
+            .collect();
+
+        Self {
+            version: VERSION,
+            maze: maze.to_text().lines().map(str::to_string).collect(),
+            start: tile(maze.start),
+            goals: maze.goals().into_iter().map(tile).collect(),
+            goal_mode: Some(state.goal_mode.label().to_lowercase()),
+            global_target: state.global_target.map(tile),
+            agents,
+            obstacles,
+        }
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn from_json(text: &str) -> io::Result<Self> {
+        let versioned: Versioned = serde_json::from_str(text).map_err(invalid)?;
+        if versioned.version != VERSION {
+            return Err(invalid("unsupported scenario version"));
+        }
+        serde_json::from_str(text).map_err(invalid)
+    }
+
+    // JSON has no NaN or infinity; serde_json would write them as null and the file
+    // could not be read back, so they are refused here instead
+    pub fn to_json(&self) -> io::Result<String> {
+        let mut floats = self.agents.iter().flat_map(|a| {
+            (a.position.into_iter())
+                .chain(a.start.into_iter().flatten())
+                .chain(a.speed)
+                .chain(a.color.into_iter().flatten())
+        });
+        let finite = floats.all(f32::is_finite)
+            && self
+                .obstacles
+                .iter()
+                .all(|o| o.interval.is_none_or(f64::is_finite));
+        if !finite {
+            return Err(invalid(
+                "positions, speeds, colors and intervals must be finite",
+            ));
+        }
+        serde_json::to_string_pretty(self).map_err(invalid)
+    }
+
+    // Replaces the current state only once the whole scenario has been checked
+    pub fn apply(&self, state: &mut AppState) -> io::Result<()> {
+        let mut maze = Maze::from_text(&self.maze.join("\n"))?;
+        let on_maze = |[x, y, z]: [usize; 3]| {
+            let n = Node::at(x, y, z);
+            if maze.contains(n) {
+                Ok(n)
+            } else {
+                Err(invalid("position outside the maze"))
+            }
+        };
+
+        let start = on_maze(self.start)?;
+        let goals = self
+            .goals
+            .iter()
+            .map(|&g| on_maze(g))
+            .collect::<io::Result<Vec<Node>>>()?;
+        let goal_mode = match &self.goal_mode {
+            None => GoalMode::Single,
+            Some(name) => [GoalMode::Single, GoalMode::Nearest, GoalMode::Tour]
+                .into_iter()
+                .find(|m| m.label().eq_ignore_ascii_case(name))
+                .ok_or_else(|| invalid("unknown goal mode"))?,
+        };
+        let global_target = self.global_target.map(on_maze).transpose()?;
+
+        let mut agents = Vec::new();
+        for a in &self.agents {
+            let position = Vec3::from_array(a.position);
+            on_maze(rounded(position))?;
+
+            let mut agent = Agent::new(node(rounded(position)), RED, a.main);
+            agent.position = position;
+            if let Some(start) = a.start {
+                agent.start_position = Vec3::from_array(start);
+            }
+            agent.initial_target = a.target.map(on_maze).transpose()?;
+            if a.heuristic >= state.heuristics.len() {
+                return Err(invalid("heuristic index out of range"));
+            }
+            agent.heuristic_index = a.heuristic;
+            if let Some(speed) = a.speed {
+                agent.speed = speed;
+            }
+            agent.color = match a.color {
+                Some(c) => c.into(),
+                None if a.main => BLUE,
+                None => RED,
+            };
+            agents.push(agent);
+        }
+
+        let mut obstacles = Vec::new();
+        for o in &self.obstacles {
+            let mut obstacle = DynamicObstacle::new(on_maze(o.position)?, maze.topology);
+            if let Some(dir) = o.direction {
+                obstacle.move_dir = dir;
+            }
+            if let Some(interval) = o.interval {
+                obstacle.move_interval = interval;
+            }
+            obstacles.push(obstacle);
+        }
+
+        let Some((&goal, extra_goals)) = goals.split_first() else {
+            return Err(invalid("a scenario needs at least one goal"));
+        };
+        // The maze text marks every goal but can't tell which one is primary
+        maze.extra_goals.clear();
+        maze.set_start(start);
+        maze.set_goal(goal);
+        for &extra in extra_goals {
+            maze.add_extra_goal(extra);
+        }
+
+        state.load_maze(maze);
+        state.goal_mode = goal_mode;
+        state.global_target = global_target;
+        state.agents = agents;
+        state.obstacles = obstacles;
+        state.replan();
+        Ok(())
+    }
+}
+
+pub fn save(state: &AppState, path: impl AsRef<Path>) -> io::Result<()> {
+    fs::write(path, Scenario::capture(state).to_json()?)
+}
+
+fn invalid(err: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("scenario: {}", err))
+}
+
+fn tile(n: Node) -> [usize; 3] {
+    [n.x, n.y, n.z]
+}
+
+fn node([x, y, z]: [usize; 3]) -> Node {
+    Node::at(x, y, z)
+}
+
+fn rounded(v: Vec3) -> [usize; 3] {
+    v.round().max(Vec3::ZERO).to_array().map(|c| c as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::maze::Tile;
+
+    fn state() -> AppState {
+        let mut config = AppConfig::default();
+        (config.grid_width, config.grid_height) = (15, 15);
+        let mut state = AppState::new(config);
+        state.start_generation();
+        if let Some(generator) = &mut state.generator {
+            while !generator.done {
+                generator.step(&mut state.maze);
+            }
+        }
+        state
+    }
+
+    // Every field set away from what loading would fill in by itself
+    fn busy_state() -> AppState {
+        let mut state = state();
+        let open: Vec<Node> = (0..15)
+            .flat_map(|y| (0..15).map(move |x| Node::new(x, y)))
+            .filter(|&n| state.maze[n] == Tile::Path)
+            .collect();
+        state.maze.add_extra_goal(open[0]);
+        state.goal_mode = GoalMode::Nearest;
+        state.global_target = Some(open[1]);
+
+        // The main agent always runs from the maze start to its goal, so only a crowd
+        // agent keeps a start and target of its own
+        let mut main = Agent::new(state.maze.start, GREEN, true);
+        main.position = vec3(open[2].x as f32 + 0.25, open[2].y as f32, 0.0);
+        main.initial_target = Some(state.maze.goal);
+        main.heuristic_index = 2;
+        main.speed = 0.1;
+        let mut crowd = Agent::new(open[5], Color::new(0.1, 0.2, 0.3, 0.5), false);
+        crowd.start_position = open[3].position();
+        crowd.initial_target = Some(open[4]);
+        state.agents = vec![main, crowd];
+
+        let mut obstacle = DynamicObstacle::new(open[6], state.maze.topology);
+        obstacle.move_dir = 3;
+        obstacle.move_interval = 0.75;
+        state.obstacles = vec![obstacle];
+        state
+    }
+
+    #[test]
+    fn every_field_survives_a_round_trip() {
+        let saved = Scenario::capture(&busy_state());
+        let text = saved.to_json().unwrap();
+        assert_eq!(Scenario::from_json(&text).unwrap(), saved);
+
+        let mut state = state();
+        Scenario::from_json(&text)
+            .unwrap()
+            .apply(&mut state)
+            .unwrap();
+        assert_eq!(Scenario::capture(&state), saved);
+        assert_eq!(state.maze.extra_goals.len(), 1);
+        assert_eq!(state.goal_mode, GoalMode::Nearest);
+        assert_eq!(state.agents[0].speed, 0.1);
+        assert_eq!(state.agents[1].color, Color::new(0.1, 0.2, 0.3, 0.5));
+        assert_eq!(
+            state.agents[1].initial_target,
+            saved.agents[1].target.map(node)
+        );
+        assert_eq!(state.obstacles[0].move_interval, 0.75);
+    }
+
+    #[test]
+    fn floats_are_written_short() {
+        let text = Scenario::capture(&busy_state()).to_json().unwrap();
+        assert!(text.contains("0.1,") || text.contains("0.1\n"));
+        assert!(!text.contains("0.10000000149"));
+    }
+
+    #[test]
+    fn missing_optional_fields_take_defaults() {
+        let saved = Scenario::capture(&busy_state());
+        let text = format!(
+            r#"{{"version": 1, "maze": {:?}, "start": {:?}, "goals": {:?},
+                "agents": [{{"position": {:?}}}], "obstacles": [{{"position": {:?}}}]}}"#,
+            saved.maze,
+            saved.start,
+            saved.goals,
+            saved.agents[0].position.map(f32::round),
+            saved.obstacles[0].position,
+        );
+        let mut state = state();
+        Scenario::from_json(&text)
+            .unwrap()
+            .apply(&mut state)
+            .unwrap();
+        assert_eq!(state.goal_mode, GoalMode::Single);
+        assert_eq!(state.global_target, None);
+        assert!(!state.agents[0].is_main);
+        assert_eq!(state.agents[0].color, RED);
+        assert_eq!(state.agents[0].heuristic_index, 0);
+    }
+
+    #[test]
+    fn non_finite_numbers_are_refused_on_save() {
+        let mut scenario = Scenario::capture(&busy_state());
+        scenario.agents[0].speed = Some(f32::NAN);
+        assert!(scenario.to_json().is_err());
+
+        let mut scenario = Scenario::capture(&busy_state());
+        scenario.agents[1].position[0] = f32::INFINITY;
+        assert!(scenario.to_json().is_err());
+
+        let mut scenario = Scenario::capture(&busy_state());
+        scenario.obstacles[0].interval = Some(f64::NEG_INFINITY);
+        assert!(scenario.to_json().is_err());
+    }
+
+    #[test]
+    fn bad_files_are_errors() {
+        let err = |text: &str| Scenario::from_json(text).unwrap_err().to_string();
+        assert!(err(r#"{"version": 2}"#).contains("unsupported scenario version"));
+        assert!(err("{").starts_with("scenario: "));
+        // Deep nesting hits serde_json's recursion limit instead of the stack
+        assert!(err(&"[".repeat(100_000)).starts_with("scenario: "));
+        let no_goals = format!(
+            r#"{{"version": 1, "maze": {:?}, "start": [1, 1, 0], "goals": [[1, -1, 0]]}}"#,
+            Scenario::capture(&state()).maze
+        );
+        assert!(err(&no_goals).starts_with("scenario: "));
+
+        let mut scenario = Scenario::capture(&busy_state());
+        scenario.agents[0].heuristic = 99;
+        assert!(scenario.apply(&mut state()).is_err());
+        let mut scenario = Scenario::capture(&busy_state());
+        scenario.goals.clear();
+        assert!(scenario.apply(&mut state()).is_err());
+        let mut scenario = Scenario::capture(&busy_state());
+        scenario.start = [100, 1, 0];
+        assert!(scenario.apply(&mut state()).is_err());
+    }
+}
@@ -0,0 +1,378 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::{fs, io};
+
+use crate::config::AppConfig;
+use crate::constants::MAX_SESSION_FRAMES;
+use crate::input::FrameInput;
+use crate::mask::Mask;
+use crate::maze::Maze;
+use crate::scenario::Scenario;
+
+const VERSION: usize = 2;
+
+// Everything needed to play a run back: the RNG seed, the config and scenario it
+// started from, the input of every frame and the contents of every file a frame
+// loaded, so a replay never reads the disk. Logged as JSON; the seed is a string
+// since JSON numbers can't hold every u64
+pub struct SessionLog {
+    pub seed: u64,
+    pub config: AppConfig,
+    pub scenario: Option<Scenario>,
+    pub frames: Vec<FrameInput>,
+    loads: Vec<Load>,
+    overflowed: bool, // Frames past MAX_SESSION_FRAMES were dropped
+}
+
+// A file as it was read, whatever its format on disk
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Loaded {
+    Maze(String),      // The maze file's text
+    Mask(Vec<String>), // ASCII art, also for a mask read from a PNG
+    Scenario(Box<Scenario>),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Load {
+    frame: usize,
+    file: Loaded,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LogFile {
+    version: usize,
+    seed: String,
+    #[serde(default)]
+    config: Option<serde_json::Value>,
+    #[serde(default)]
+    scenario: Option<Scenario>,
+    frames: Vec<FrameInput>,
+    #[serde(default)]
+    loads: Vec<Load>,
+}
+
+// Read first, so a log from another version is reported as such and not as a bad field
+#[derive(Deserialize)]
+struct Versioned {
+    version: usize,
+}
+
+impl SessionLog {
+    pub fn new(seed: u64, config: AppConfig) -> Self {
+        Self {
+            seed,
+            config,
+            scenario: None,
+            frames: Vec::new(),
+            loads: Vec::new(),
+            overflowed: false,
+        }
+    }
+
+    // Logs a frame until MAX_SESSION_FRAMES; true on the frame that fills the log
+    pub fn push(&mut self, input: FrameInput) -> bool {
+        if self.frames.len() >= MAX_SESSION_FRAMES {
+            self.overflowed = true;
+            return false;
+        }
+        self.frames.push(input);
+        self.frames.len() == MAX_SESSION_FRAMES
+    }
+
+    // The maze file, as read by the current frame
+    pub fn read_maze(&mut self, replay: &mut Replay, path: &str) -> io::Result<Maze> {
+        let file = self.read(
+            replay,
+            path,
+            |f| matches!(f, Loaded::Maze(_)),
+            || Ok(Loaded::Maze(fs::read_to_string(path)?)),
+        )?;
+        match file {
+            Loaded::Maze(text) => Maze::from_text(&text),
+            _ => Err(invalid("logged file isn't a maze")),
+        }
+    }
+
+    // The first of `paths` that exists, as a mask
+    pub fn read_mask(&mut self, replay: &mut Replay, paths: &[&str]) -> io::Result<Mask> {
+        let names = paths.join(", ");
+        let file = self.read(
+            replay,
+            &names,
+            |f| matches!(f, Loaded::Mask(_)),
+            || {
+                let path = paths
+                    .iter()
+                    .find(|p| Path::new(p).exists())
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::NotFound,
+                            format!("no mask file found ({})", names),
+                        )
+                    })?;
+                let mask = Mask::load(path)
+                    .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path, err)))?;
+                Ok(Loaded::Mask(mask.to_ascii()))
+            },
+        )?;
+        match file {
+            Loaded::Mask(rows) => Mask::from_ascii(&rows.join("\n")),
+            _ => Err(invalid("logged file isn't a mask")),
+        }
+    }
+
+    pub fn read_scenario(&mut self, replay: &mut Replay, path: &str) -> io::Result<Scenario> {
+        let file = self.read(
+            replay,
+            path,
+            |f| matches!(f, Loaded::Scenario(_)),
+            || Ok(Loaded::Scenario(Box::new(Scenario::read(path)?))),
+        )?;
+        match file {
+            Loaded::Scenario(scenario) => Ok(*scenario),
+            _ => Err(invalid("logged file isn't a scenario")),
+        }
+    }
+
+    // Live frames read the disk; replayed ones take what the log kept for them, so a
+    // file that failed to load live is missing on replay too. Either way what was read
+    // is logged again, keeping a saved replay complete
+    fn read(
+        &mut self,
+        replay: &mut Replay,
+        path: &str,
+        kind: fn(&Loaded) -> bool,
+        from_disk: impl FnOnce() -> io::Result<Loaded>,
+    ) -> io::Result<Loaded> {
+        let file = if replay.replayed {
+            replay.take(kind).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{} wasn't loaded in the logged session", path),
+                )
+            })?
+        } else {
+            from_disk()?
+        };
+        if let Some(frame) = self.frames.len().checked_sub(1)
+            && !self.overflowed
+        {
+            self.loads.push(Load {
+                frame,
+                file: file.clone(),
+            });
+        }
+        Ok(file)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let log = LogFile {
+            version: VERSION,
+            seed: self.seed.to_string(),
+            config: Some(self.config.to_json()),
+            scenario: self.scenario.clone(),
+            frames: self.frames.clone(),
+            loads: self.loads.clone(),
+        };
+        fs::write(path, serde_json::to_string_pretty(&log).map_err(invalid)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let versioned: Versioned = serde_json::from_str(&text).map_err(invalid)?;
+        if versioned.version != VERSION {
+            return Err(invalid("unsupported session version"));
+        }
+        let log: LogFile = serde_json::from_str(&text).map_err(invalid)?;
+        let seed = log
+            .seed
+            .parse()
+            .map_err(|_| invalid("'seed' must be a number in a string"))?;
+        // Logs from before configs were saved ran on the defaults
+        let mut config = AppConfig::default();
+        if let Some(value) = &log.config {
+            config
+                .apply_json(value)
+                .map_err(|err| invalid(format!("config: {}", err)))?;
+        }
+        Ok(Self {
+            seed,
+            config,
+            scenario: log.scenario,
+            frames: log.frames,
+            loads: log.loads,
+            overflowed: false,
+        })
+    }
+
+    // Frames to feed back in order; live input takes over once they run out
+    pub fn replay(self) -> Replay {
+        Replay {
+            frames: self.frames.into(),
+            loads: self.loads.into(),
+            next: 0,
+            replayed: false,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Replay {
+    frames: VecDeque<FrameInput>,
+    loads: VecDeque<Load>,
+    next: usize,    // Index of the next logged frame
+    replayed: bool, // Whether the frame taken last came from the log
+}
+
+impl Replay {
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn front(&self) -> Option<&FrameInput> {
+        self.frames.front()
+    }
+
+    pub fn pop_front(&mut self) -> Option<FrameInput> {
+        let frame = self.frames.pop_front();
+        self.replayed = frame.is_some();
+        if self.replayed {
+            self.next += 1;
+            // Loads of kinds the frame didn't ask for again are dropped with it
+            while self.loads.front().is_some_and(|l| l.frame + 1 < self.next) {
+                self.loads.pop_front();
+            }
+        }
+        frame
+    }
+
+    // The file of this kind that the frame just replayed loaded, if it loaded one
+    fn take(&mut self, kind: fn(&Loaded) -> bool) -> Option<Loaded> {
+        let i = self
+            .loads
+            .iter()
+            .take_while(|l| l.frame + 1 == self.next)
+            .position(|l| kind(&l.file))?;
+        self.loads.remove(i).map(|l| l.file)
+    }
+}
+
+fn invalid(err: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("session: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp(name: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("maze-session-{}-{}", std::process::id(), name));
+        path.to_string_lossy().into_owned()
+    }
+
+    fn frame(dt: f32) -> FrameInput {
+        FrameInput {
+            dt,
+            ..Default::default()
+        }
+    }
+
+    const MAZE: &str = "#####\n#S..#\n###.#\n#G..#\n#####\n";
+
+    // Two live frames: the first loads a maze and fails to find a mask, the second
+    // loads a mask. Saved, then replayed with the files gone
+    fn logged(name: &str) -> SessionLog {
+        let (maze, mask) = (
+            temp(&format!("{}.txt", name)),
+            temp(&format!("{}-mask.txt", name)),
+        );
+        fs::write(&maze, MAZE).unwrap();
+        let mut live = SessionLog::new(7, AppConfig::default());
+        let mut replay = Replay::default();
+
+        live.push(frame(0.1));
+        live.read_maze(&mut replay, &maze).unwrap();
+        assert!(live.read_mask(&mut replay, &[&mask]).is_err());
+        live.push(frame(0.2));
+        fs::write(&mask, " ## \n####\n").unwrap();
+        live.read_mask(&mut replay, &[&mask]).unwrap();
+
+        let log = temp(&format!("{}.json", name));
+        live.save(&log).unwrap();
+        fs::remove_file(&maze).unwrap();
+        fs::remove_file(&mask).unwrap();
+        SessionLog::load(&log).unwrap()
+    }
+
+    #[test]
+    fn replays_take_files_from_the_log() {
+        let log = logged("files");
+        assert_eq!(log.seed, 7);
+        let mut again = SessionLog::new(log.seed, log.config.clone());
+        let mut replay = log.replay();
+
+        again.push(replay.pop_front().unwrap());
+        let maze = again.read_maze(&mut replay, "gone.txt").unwrap();
+        assert_eq!(maze.to_text(), MAZE);
+        // Missing live, so missing on replay, without taking the next frame's mask
+        let err = again.read_mask(&mut replay, &["gone.png"]).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("wasn't loaded in the logged session")
+        );
+
+        again.push(replay.pop_front().unwrap());
+        assert!(again.read_scenario(&mut replay, "gone.json").is_err());
+        let mask = again.read_mask(&mut replay, &["gone.png"]).unwrap();
+        assert_eq!(mask.to_ascii(), [".##.", "####"]);
+        assert!(replay.is_empty());
+
+        // Logged again while replaying, so the replay can itself be saved and replayed
+        assert_eq!(again.loads.len(), 2);
+        assert_eq!(
+            again.loads.iter().map(|l| l.frame).collect::<Vec<_>>(),
+            [0, 1]
+        );
+    }
+
+    #[test]
+    fn live_frames_after_a_replay_read_the_disk() {
+        let mut replay = logged("live").replay();
+        while replay.pop_front().is_some() {}
+        let mut session = SessionLog::new(0, AppConfig::default());
+        session.push(frame(0.1));
+        let err = session
+            .read_maze(&mut replay, &temp("missing.txt"))
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(!err.to_string().contains("logged session"));
+    }
+
+    #[test]
+    fn the_log_stops_growing_at_the_cap() {
+        let mut session = SessionLog::new(0, AppConfig::default());
+        let filled = (0..MAX_SESSION_FRAMES + 10)
+            .filter(|&i| session.push(frame(i as f32)))
+            .collect::<Vec<_>>();
+        assert_eq!(filled, [MAX_SESSION_FRAMES - 1]);
+        assert_eq!(session.frames.len(), MAX_SESSION_FRAMES);
+
+        // Loads by frames that weren't logged aren't kept either
+        let path = temp("capped.txt");
+        fs::write(&path, MAZE).unwrap();
+        session.read_maze(&mut Replay::default(), &path).unwrap();
+        assert!(session.loads.is_empty());
+    }
+
+    #[test]
+    fn other_versions_are_refused() {
+        let path = temp("old.json");
+        fs::write(&path, r#"{"version": 1, "seed": "1", "frames": []}"#).unwrap();
+        let err = SessionLog::load(&path).err().unwrap();
+        assert!(err.to_string().contains("unsupported session version"));
+    }
+}
@@ -0,0 +1,442 @@
+use macroquad::prelude::*;
+
+use crate::config::AppConfig;
+use crate::constants::{
+    AGENT_SPEED_RANGE, MAX_HEURISTIC_WEIGHT, MAX_LANDMARKS, MAX_MAZE_SIZE, MAX_STEPS_PER_FRAME,
+    MAX_TRAIL_LENGTH, MAZE_HEIGHT, MAZE_WIDTH, MIN_MAZE_SIZE, OBSTACLE_INTERVAL_RANGE,
+};
+use crate::generator::Algorithm;
+use crate::input::FrameInput;
+use crate::landmarks::Selection;
+use crate::maze::Connectivity;
+
+const PANEL_WIDTH: f32 = 320.0;
+const ROW_HEIGHT: f32 = 26.0;
+const PADDING: f32 = 10.0;
+const LABEL_WIDTH: f32 = 130.0;
+const PANEL_COLOR: Color = Color::new(0.05, 0.05, 0.1, 0.92);
+
+// Size presets, from a maze you can trace by eye to one that stresses the solver
+const PRESETS: [(&str, usize, usize); 3] = [
+    ("Tiny", 21, 11),
+    ("Default", MAZE_WIDTH, MAZE_HEIGHT),
+    ("Huge", 1001, 1001),
+];
+
+const ALGORITHMS: [(Algorithm, &str); 5] = [
+    (Algorithm::RecursiveBacktracker, "Backtracker"),
+    (Algorithm::Prims, "Prim's"),
+    (Algorithm::Braid, "Braid"),
+    (Algorithm::Eller, "Eller's"),
+    (Algorithm::Kruskal, "Kruskal's"),
+];
+
+// What the panel asks the main loop to do after it changed the config
+pub enum SettingsAction {
+    Resize { width: usize, height: usize },
+    Regenerate, // Another generator was picked
+    Replan,     // The search changed: restart the solver and reroute the agents
+    Remap,      // Connectivity or landmarks changed: heuristics look at the maze again, then replan
+    Retune,     // Agent or obstacle settings changed
+}
+
+// Shapes recorded while laying the panel out, drawn later on top of the scene
+enum Shape {
+    Rect(Rect, Color),
+    Text(String, Vec2, Color),
+}
+
+// Tiny immediate-mode layout: each call takes the next row, reacts to this frame's
+// input and records what to draw. It runs on `FrameInput`, so replays drive it too
+struct Ui<'a> {
+    input: &'a FrameInput,
+    active: &'a mut Option<usize>, // Slider being dragged
+    open: &'a mut Option<usize>,   // Dropdown showing its options
+    shapes: Vec<Shape>,
+    popup: Vec<Shape>,        // Open dropdown list, drawn over the rows below it
+    popup_rect: Option<Rect>, // Where that list takes the clicks
+    x: f32,
+    y: f32,
+    width: f32,
+    next_id: usize,
+}
+
+impl Ui<'_> {
+    fn row(&mut self) -> Rect {
+        let rect = Rect::new(self.x, self.y, self.width, ROW_HEIGHT);
+        self.y += ROW_HEIGHT;
+        rect
+    }
+
+    fn id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    // A press on `rect` that an open dropdown list isn't covering
+    fn pressed(&self, rect: Rect) -> bool {
+        let pointer = self.input.pointer;
+        self.input.button_pressed(MouseButton::Left)
+            && rect.contains(pointer)
+            && !self.popup_rect.is_some_and(|r| r.contains(pointer))
+    }
+
+    fn text(&mut self, text: String, at: Vec2, color: Color) {
+        self.shapes.push(Shape::Text(text, at, color));
+    }
+
+    fn heading(&mut self, text: &str) {
+        let row = self.row();
+        self.text(text.to_string(), vec2(row.x, row.y + 19.0), YELLOW);
+    }
+
+    // Drag anywhere on the track; the value snaps to `step` above `min`. Returns the
+    // new value on the frames it changes
+    fn slider(
+        &mut self,
+        label: &str,
+        value: f32,
+        (min, max, step): (f32, f32, f32),
+        show: impl Fn(f32) -> String,
+    ) -> Option<f32> {
+        let id = self.id();
+        let row = self.row();
+        let track = Rect::new(
+            row.x + LABEL_WIDTH,
+            row.y + 9.0,
+            row.w - LABEL_WIDTH - 60.0,
+            8.0,
+        );
+
+        if self.pressed(Rect::new(track.x, row.y, track.w, row.h)) {
+            *self.active = Some(id);
+        }
+        let mut changed = None;
+        if *self.active == Some(id) {
+            let t = ((self.input.pointer.x - track.x) / track.w).clamp(0.0, 1.0);
+            let snapped = (min + (t * (max - min) / step).round() * step).min(max);
+            if snapped != value {
+                changed = Some(snapped);
+            }
+        }
+
+        let value = changed.unwrap_or(value);
+        let t = ((value - min) / (max - min)).clamp(0.0, 1.0);
+        self.text(label.to_string(), vec2(row.x, row.y + 18.0), WHITE);
+        self.shapes.push(Shape::Rect(track, DARKGRAY));
+        self.shapes.push(Shape::Rect(
+            Rect::new(track.x, track.y, track.w * t, track.h),
+            SKYBLUE,
+        ));
+        self.shapes.push(Shape::Rect(
+            Rect::new(
+                track.x + track.w * t - 3.0,
+                track.y - 4.0,
+                6.0,
+                track.h + 8.0,
+            ),
+            WHITE,
+        ));
+        self.text(show(value), vec2(track.right() + 10.0, row.y + 18.0), WHITE);
+        changed
+    }
+
+    // Shows the current choice; clicking it lists every option over the rows below.
+    // Returns the option picked
+    fn dropdown(&mut self, label: &str, options: &[&str], current: usize) -> Option<usize> {
+        let id = self.id();
+        let row = self.row();
+        let field = Rect::new(
+            row.x + LABEL_WIDTH,
+            row.y + 3.0,
+            row.w - LABEL_WIDTH,
+            row.h - 6.0,
+        );
+        let hovered = field.contains(self.input.pointer);
+        let was_open = *self.open == Some(id);
+        if self.pressed(field) {
+            *self.open = if was_open { None } else { Some(id) };
+        }
+
+        self.text(label.to_string(), vec2(row.x, row.y + 18.0), WHITE);
+        let color = if hovered { GRAY } else { DARKGRAY };
+        self.shapes.push(Shape::Rect(field, color));
+        self.text(
+            format!("{} v", options[current]),
+            vec2(field.x + 6.0, field.y + 15.0),
+            WHITE,
+        );
+        if !was_open {
+            return None;
+        }
+
+        let list = Rect::new(
+            field.x,
+            field.bottom(),
+            field.w,
+            options.len() as f32 * field.h,
+        );
+        let mut picked = None;
+        for (i, option) in options.iter().enumerate() {
+            let rect = Rect::new(list.x, list.y + i as f32 * field.h, list.w, field.h);
+            let hovered = rect.contains(self.input.pointer);
+            if hovered && self.input.button_pressed(MouseButton::Left) {
+                picked = Some(i);
+            }
+            let color = match (hovered, i == current) {
+                (true, _) => GRAY,
+                (false, true) => DARKBLUE,
+                (false, false) => DARKGRAY,
+            };
+            self.popup.push(Shape::Rect(rect, BLACK));
+            self.popup.push(Shape::Rect(
+                Rect::new(rect.x, rect.y + 1.0, rect.w, rect.h - 1.0),
+                color,
+            ));
+            self.popup.push(Shape::Text(
+                option.to_string(),
+                vec2(rect.x + 6.0, rect.y + 15.0),
+                WHITE,
+            ));
+        }
+        // Any click away from the field closes the list
+        if self.input.button_pressed(MouseButton::Left) && !hovered {
+            *self.open = None;
+        }
+        self.popup_rect = Some(list);
+        picked
+    }
+
+    // A row of buttons; returns the index of the one clicked
+    fn buttons(&mut self, labels: &[&str]) -> Option<usize> {
+        let row = self.row();
+        let w = row.w / labels.len() as f32;
+        let mut clicked = None;
+        for (i, label) in labels.iter().enumerate() {
+            let rect = Rect::new(
+                row.x + i as f32 * w + 2.0,
+                row.y + 3.0,
+                w - 4.0,
+                row.h - 6.0,
+            );
+            if self.pressed(rect) {
+                clicked = Some(i);
+            }
+            let color = if rect.contains(self.input.pointer) {
+                GRAY
+            } else {
+                DARKGRAY
+            };
+            self.shapes.push(Shape::Rect(rect, color));
+            self.text(label.to_string(), vec2(rect.x + 6.0, rect.y + 15.0), WHITE);
+        }
+        clicked
+    }
+}
+
+pub struct Settings {
+    pub open: bool,
+    pub width: usize, // Pending maze size, applied on demand
+    pub height: usize,
+    active: Option<usize>,
+    dropdown: Option<usize>,
+    shapes: Vec<Shape>,
+    rect: Rect,
+    popup_rect: Option<Rect>,
+}
+
+impl Settings {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            open: false,
+            width: config.grid_width,
+            height: config.grid_height,
+            active: None,
+            dropdown: None,
+            shapes: Vec::new(),
+            rect: Rect::default(),
+            popup_rect: None,
+        }
+    }
+
+    // Opening starts from the maze as it is now, which may have been loaded from a file
+    pub fn toggle(&mut self, config: &AppConfig) {
+        self.open = !self.open;
+        self.width = config.grid_width;
+        self.height = config.grid_height;
+        self.dropdown = None;
+    }
+
+    // True when the pointer is over the open panel, so clicks there stay off the maze
+    pub fn captures(&self, input: &FrameInput) -> bool {
+        let over = |r: Rect| r.contains(input.pointer);
+        self.open && (over(self.rect) || self.popup_rect.is_some_and(over) || self.active.is_some())
+    }
+
+    // Lays the panel out and applies what the user changed to `config`
+    pub fn update(&mut self, input: &FrameInput, config: &mut AppConfig) -> Option<SettingsAction> {
+        if !input.button_down(MouseButton::Left) {
+            self.active = None;
+        }
+        if self.dropdown.is_none() {
+            self.popup_rect = None;
+        }
+        if !self.open {
+            return None;
+        }
+
+        let area = config.maze_area();
+        let x = area.right() - PANEL_WIDTH - PADDING;
+        let mut ui = Ui {
+            input,
+            active: &mut self.active,
+            open: &mut self.dropdown,
+            shapes: Vec::new(),
+            popup: Vec::new(),
+            popup_rect: self.popup_rect,
+            x: x + PADDING,
+            y: area.y + 2.0 * PADDING,
+            width: PANEL_WIDTH - 2.0 * PADDING,
+            next_id: 0,
+        };
+        let mut action = None;
+
+        ui.heading("Maze");
+        let names = ALGORITHMS.map(|(_, name)| name);
+        let current = ALGORITHMS
+            .iter()
+            .position(|(a, _)| *a == config.algorithm)
+            .unwrap_or(0);
+        if let Some(i) = ui.dropdown("Generator", &names, current) {
+            config.algorithm = ALGORITHMS[i].0;
+            action = Some(SettingsAction::Regenerate);
+        }
+        // Eller's join chance / share of dead ends Braid opens, used from the next maze on
+        if let Some(v) = ui.slider("Density", config.density, (0.0, 1.0, 0.05), |v| {
+            format!("{:.2}", v)
+        }) {
+            config.density = v;
+        }
+        let sizes = (MIN_MAZE_SIZE as f32, MAX_MAZE_SIZE as f32, 2.0);
+        let tiles = |v: f32| (v as usize).to_string();
+        if let Some(v) = ui.slider("Width", self.width as f32, sizes, tiles) {
+            self.width = v as usize;
+        }
+        if let Some(v) = ui.slider("Height", self.height as f32, sizes, tiles) {
+            self.height = v as usize;
+        }
+        let names: Vec<&str> = PRESETS.iter().map(|p| p.0).collect();
+        if let Some(i) = ui.buttons(&names) {
+            (self.width, self.height) = (PRESETS[i].1, PRESETS[i].2);
+        }
+        if ui.buttons(&["Apply & Regenerate"]).is_some() {
+            action = Some(SettingsAction::Resize {
+                width: self.width,
+                height: self.height,
+            });
+        }
+
+        ui.heading("Search");
+        if let Some(v) = ui.slider(
+            "Heuristic weight",
+            config.heuristic_weight,
+            (1.0, MAX_HEURISTIC_WEIGHT, 0.1),
+            |v| format!("{:.1}", v),
+        ) {
+            config.heuristic_weight = v;
+            action = Some(SettingsAction::Replan);
+        }
+        let names = Connectivity::ALL.map(Connectivity::label);
+        let current = Connectivity::ALL
+            .iter()
+            .position(|c| *c == config.connectivity)
+            .unwrap_or(0);
+        if let Some(i) = ui.dropdown("Connectivity", &names, current) {
+            config.connectivity = Connectivity::ALL[i];
+            action = Some(SettingsAction::Remap);
+        }
+        if let Some(v) = ui.slider(
+            "ALT landmarks",
+            config.landmarks as f32,
+            (1.0, MAX_LANDMARKS as f32, 1.0),
+            |v| v.to_string(),
+        ) {
+            config.landmarks = v as usize;
+            action = Some(SettingsAction::Remap);
+        }
+        let names = Selection::ALL.map(Selection::label);
+        let current = Selection::ALL
+            .iter()
+            .position(|s| *s == config.landmark_selection)
+            .unwrap_or(0);
+        if let Some(i) = ui.dropdown("Landmark pick", &names, current) {
+            config.landmark_selection = Selection::ALL[i];
+            action = Some(SettingsAction::Remap);
+        }
+        // Powers of two, like the - / = keys
+        let max_power = MAX_STEPS_PER_FRAME.ilog2() as f32;
+        let power = config.simulation_speed.max(1).ilog2() as f32;
+        if let Some(v) = ui.slider("Steps / frame", power, (0.0, max_power, 1.0), |v| {
+            (1usize << v as u32).to_string()
+        }) {
+            config.simulation_speed = 1 << v as u32;
+            config.step_delay = 0.0;
+        }
+
+        ui.heading("Agents");
+        if let Some(v) = ui.slider(
+            "Speed (tiles/s)",
+            config.agent_speed,
+            (AGENT_SPEED_RANGE.0, AGENT_SPEED_RANGE.1, 0.5),
+            |v| format!("{:.1}", v),
+        ) {
+            config.agent_speed = v;
+            action = Some(SettingsAction::Retune);
+        }
+        if let Some(v) = ui.slider(
+            "Obstacle step (s)",
+            config.obstacle_interval as f32,
+            (
+                OBSTACLE_INTERVAL_RANGE.0 as f32,
+                OBSTACLE_INTERVAL_RANGE.1 as f32,
+                0.1,
+            ),
+            |v| format!("{:.1}", v),
+        ) {
+            config.obstacle_interval = v as f64;
+            action = Some(SettingsAction::Retune);
+        }
+        if let Some(v) = ui.slider(
+            "Trail length",
+            config.trail_length as f32,
+            (0.0, MAX_TRAIL_LENGTH as f32, 10.0),
+            tiles,
+        ) {
+            config.trail_length = v as usize;
+            action = Some(SettingsAction::Retune);
+        }
+
+        self.rect = Rect::new(x, area.y + PADDING, PANEL_WIDTH, ui.y - area.y);
+        self.popup_rect = ui.popup_rect;
+        self.shapes = ui.shapes;
+        self.shapes.append(&mut ui.popup);
+        action
+    }
+
+    pub fn draw(&self) {
+        if !self.open {
+            return;
+        }
+        let r = self.rect;
+        draw_rectangle(r.x, r.y, r.w, r.h, PANEL_COLOR);
+        draw_rectangle_lines(r.x, r.y, r.w, r.h, 1.0, GRAY);
+        for shape in &self.shapes {
+            match shape {
+                Shape::Rect(rect, color) => draw_rectangle(rect.x, rect.y, rect.w, rect.h, *color),
+                Shape::Text(text, at, color) => {
+                    draw_text(text, at.x, at.y, 20.0, *color);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,411 @@
+use macroquad::prelude::*;
+
+use crate::node::Node;
+
+// Directions are ordered so that `(d + len / 2) % len` is the opposite of `d`
+const SQUARE_DIRS: [(isize, isize); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+
+// Odd-r layout: odd rows are shifted half a tile to the right.
+// Order: E, NE, NW, W, SW, SE
+const HEX_EVEN_ROW_DIRS: [(isize, isize); 6] =
+    [(1, 0), (0, -1), (-1, -1), (-1, 0), (-1, 1), (0, 1)];
+const HEX_ODD_ROW_DIRS: [(isize, isize); 6] = [(1, 0), (1, -1), (0, -1), (-1, 0), (0, 1), (1, 1)];
+
+const HEX_ROW_SPACING: f32 = 0.866_025_4; // sqrt(3) / 2
+
+// Polar layout: tile row y is a ring (odd y holds cells, even y the walls between rings,
+// the last one being the outer wall), tile x runs clockwise around it and wraps. Order: CW, out, out (second child), CCW, in, -
+const POLAR_DIRECTIONS: usize = 6;
+const POLAR_INNER_SECTORS: usize = 6;
+const POLAR_CELL_SPAN: f32 = 0.75; // Share of a sector taken by the cell, the rest is its wall
+const NO_TILE: (isize, isize) = (-1, -1);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Topology {
+    Square,
+    Hex,
+    Polar { rings: usize },
+}
+
+// Cells per ring; a ring doubles them once the previous count would make cells too wide
+pub fn polar_sectors(ring: usize) -> usize {
+    let mut sectors = POLAR_INNER_SECTORS;
+    for r in 1..=ring {
+        let arc = std::f32::consts::TAU * (r as f32 + 1.5) / sectors as f32;
+        if arc >= 2.0 {
+            sectors *= 2;
+        }
+    }
+    sectors
+}
+
+// Tiles in a row: odd rows belong to ring y / 2, even rows to the ring outside them
+fn polar_row_tiles(y: usize) -> usize {
+    2 * polar_sectors(y / 2)
+}
+
+impl Topology {
+    pub fn label(self) -> &'static str {
+        match self {
+            Topology::Square => "Square",
+            Topology::Hex => "Hex",
+            Topology::Polar { .. } => "Polar",
+        }
+    }
+
+    // Square, hex or polar; a polar grid gets as many rings as fit width x height
+    pub fn from_name(name: &str, width: usize, height: usize) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "square" => Some(Topology::Square),
+            "hex" => Some(Topology::Hex),
+            "polar" => Some(Topology::polar(width, height)),
+            _ => None,
+        }
+    }
+
+    // Polar grid with as many rings as fit a width x height tile grid (and its window)
+    pub fn polar(width: usize, height: usize) -> Self {
+        let mut rings = 1;
+        while 4 * (rings + 1) + 5 <= height && 2 * polar_sectors(rings + 1) + 2 <= width {
+            rings += 1;
+        }
+        Topology::Polar { rings }
+    }
+
+    // Tiles that belong to the layout at all; rectangular layouts use every tile
+    pub fn contains(self, x: isize, y: isize) -> bool {
+        match self {
+            Topology::Square | Topology::Hex => true,
+            Topology::Polar { rings } => {
+                y >= 1
+                    && y <= 2 * rings as isize
+                    && x >= 1
+                    && x <= polar_row_tiles(y as usize) as isize
+            }
+        }
+    }
+
+    // Fixed offsets of the rectangular layouts
+    pub fn directions(self, node: Node) -> &'static [(isize, isize)] {
+        match self {
+            Topology::Square | Topology::Polar { .. } => &SQUARE_DIRS,
+            Topology::Hex if node.y.is_multiple_of(2) => &HEX_EVEN_ROW_DIRS,
+            Topology::Hex => &HEX_ODD_ROW_DIRS,
+        }
+    }
+
+    pub fn direction_count(self) -> usize {
+        match self {
+            Topology::Square => SQUARE_DIRS.len(),
+            Topology::Hex => HEX_EVEN_ROW_DIRS.len(),
+            Topology::Polar { .. } => POLAR_DIRECTIONS,
+        }
+    }
+
+    pub fn step(self, node: Node, dir: usize) -> (isize, isize) {
+        if let Topology::Polar { .. } = self {
+            return Self::polar_step(node, dir);
+        }
+        let (dx, dy) = self.directions(node)[dir];
+        (node.x as isize + dx, node.y as isize + dy)
+    }
+
+    fn polar_step(node: Node, dir: usize) -> (isize, isize) {
+        let (x, y) = (node.x as isize, node.y as isize);
+        let tiles = polar_row_tiles(node.y) as isize;
+
+        match dir {
+            0 => (if x >= tiles { 1 } else { x + 1 }, y),
+            3 => (if x <= 1 { tiles } else { x - 1 }, y),
+            1 | 2 => {
+                let outer = polar_row_tiles(node.y + 1) as isize;
+                if outer == tiles {
+                    // Only a ring's inner wall row leads out to both of its cells' children
+                    if dir == 1 || y % 2 == 0 {
+                        (x, y + 1)
+                    } else {
+                        NO_TILE
+                    }
+                } else if x % 2 == 0 {
+                    // A wall between two cells meets the corner above it
+                    if dir == 1 { (2 * x, y + 1) } else { NO_TILE }
+                } else if dir == 1 {
+                    (2 * x - 1, y + 1)
+                } else {
+                    (2 * x + 1, y + 1)
+                }
+            }
+            4 if y > 1 => {
+                let inner = polar_row_tiles(node.y - 1) as isize;
+                if inner == tiles {
+                    (x, y - 1)
+                } else if x % 2 == 1 {
+                    (2 * ((x - 1) / 4) + 1, y - 1)
+                } else {
+                    (x / 2, y - 1)
+                }
+            }
+            _ => NO_TILE,
+        }
+    }
+
+    // Generators carve on a lattice of cells two steps apart; the tile between is the wall
+    pub fn is_cell(self, node: Node) -> bool {
+        match self {
+            Topology::Square | Topology::Polar { .. } => node.x % 2 == 1 && node.y % 2 == 1,
+            Topology::Hex => {
+                let (q, r) = Self::hex_axial(node);
+                q % 2 == 0 && r % 2 == 0
+            }
+        }
+    }
+
+    // Axial (q, r) coordinates of an odd-r offset tile
+    pub fn hex_axial(node: Node) -> (isize, isize) {
+        let r = node.y as isize;
+        let q = node.x as isize - (r - (r & 1)) / 2;
+        (q, r)
+    }
+
+    // Tile center in pixels; fractional positions interpolate the odd-row shift
+    pub fn center(self, x: f32, y: f32, cell_size: f32) -> Vec2 {
+        match self {
+            Topology::Square => vec2((x + 0.5) * cell_size, (y + 0.5) * cell_size),
+            Topology::Hex => {
+                let row = y.floor();
+                let t = y - row;
+                let parity = |r: f32| (r as isize & 1) as f32;
+                let shift = 0.5 * (parity(row) * (1.0 - t) + parity(row + 1.0) * t);
+                vec2(
+                    (x + 0.5 + shift) * cell_size,
+                    (y * HEX_ROW_SPACING + 0.5) * cell_size,
+                )
+            }
+            Topology::Polar { rings } => {
+                // Between rows of different sector counts, blend the angle from both
+                let row = y.floor().max(0.0);
+                let t = y - row;
+                let turn = |r: f32| {
+                    let sectors = polar_row_tiles(r as usize) as f32 / 2.0;
+                    ((x - 1.0) / 2.0 + POLAR_CELL_SPAN / 2.0) / sectors
+                };
+                let angle = turn(row) * (1.0 - t) + turn(row + 1.0) * t;
+                Self::polar_point(rings, y + 2.0, angle, cell_size)
+            }
+        }
+    }
+
+    // Pixel position at `radius` tiles from the middle, `turn` of a full circle clockwise
+    // from the top
+    fn polar_point(rings: usize, radius: f32, turn: f32, cell_size: f32) -> Vec2 {
+        let middle = Self::polar_radius(rings) * cell_size;
+        let theta = turn * std::f32::consts::TAU - std::f32::consts::FRAC_PI_2;
+        vec2(middle, middle) + Vec2::from_angle(theta) * radius * cell_size
+    }
+
+    // Outer radius in tiles: the outer wall is row 2 * rings
+    fn polar_radius(rings: usize) -> f32 {
+        2.0 * rings as f32 + 2.5
+    }
+
+    // Start and end of tile x's arc in its row, in sectors
+    fn polar_arc(x: usize) -> (f32, f32) {
+        let sector = ((x - 1) / 2) as f32;
+        if x % 2 == 1 {
+            (sector, sector + POLAR_CELL_SPAN)
+        } else {
+            (sector + POLAR_CELL_SPAN, sector + 1.0)
+        }
+    }
+
+    pub fn tile_at(self, pos: Vec2, cell_size: f32) -> (isize, isize) {
+        match self {
+            Topology::Square => (
+                (pos.x / cell_size).floor() as isize,
+                (pos.y / cell_size).floor() as isize,
+            ),
+            Topology::Hex => {
+                let y = (pos.y / (cell_size * HEX_ROW_SPACING)).floor() as isize;
+                let shift = if y & 1 == 1 { 0.5 } else { 0.0 };
+                ((pos.x / cell_size - shift).floor() as isize, y)
+            }
+            Topology::Polar { rings } => {
+                let middle = Self::polar_radius(rings) * cell_size;
+                let offset = pos - vec2(middle, middle);
+                let y = (offset.length() / cell_size - 1.5).floor();
+                if y < 0.0 {
+                    return NO_TILE;
+                }
+                let turn = (offset.y.atan2(offset.x) + std::f32::consts::FRAC_PI_2)
+                    .rem_euclid(std::f32::consts::TAU)
+                    / std::f32::consts::TAU;
+                let sectors = turn * (polar_row_tiles(y as usize) / 2) as f32;
+                let x = 2 * sectors.floor() as isize
+                    + if sectors.fract() < POLAR_CELL_SPAN {
+                        1
+                    } else {
+                        2
+                    };
+                (x, y as isize)
+            }
+        }
+    }
+
+    pub fn pixel_width(self, cols: usize, cell_size: f32) -> f32 {
+        match self {
+            Topology::Square => cols as f32 * cell_size,
+            Topology::Hex => (cols as f32 + 0.5) * cell_size,
+            Topology::Polar { rings } => 2.0 * Self::polar_radius(rings) * cell_size,
+        }
+    }
+
+    pub fn pixel_height(self, rows: usize, cell_size: f32) -> f32 {
+        match self {
+            Topology::Square => rows as f32 * cell_size,
+            Topology::Hex => ((rows as f32 - 1.0) * HEX_ROW_SPACING + 1.0) * cell_size,
+            Topology::Polar { rings } => 2.0 * Self::polar_radius(rings) * cell_size,
+        }
+    }
+
+    // Polar tiles are ring segments, so they are drawn from the tile itself rather than
+    // its center; `origin` is the top-left corner of the floor
+    pub fn draw_polar_tile(self, node: Node, origin: Vec2, cell_size: f32, color: Color) {
+        let Topology::Polar { rings } = self else {
+            return;
+        };
+        let segment = Self::polar_segment(rings, node, cell_size);
+        for i in 0..segment.len() / 2 - 1 {
+            let (inner, outer) = (segment[2 * i], segment[2 * i + 1]);
+            let (next_inner, next_outer) = (segment[2 * i + 2], segment[2 * i + 3]);
+            draw_triangle(origin + inner, origin + outer, origin + next_outer, color);
+            draw_triangle(
+                origin + inner,
+                origin + next_outer,
+                origin + next_inner,
+                color,
+            );
+        }
+    }
+
+    pub fn draw_polar_tile_lines(
+        self,
+        node: Node,
+        origin: Vec2,
+        cell_size: f32,
+        thickness: f32,
+        color: Color,
+    ) {
+        let Topology::Polar { rings } = self else {
+            return;
+        };
+        let segment = Self::polar_segment(rings, node, cell_size);
+        let n = segment.len();
+        let line = |a: Vec2, b: Vec2| {
+            let (a, b) = (origin + a, origin + b);
+            draw_line(a.x, a.y, b.x, b.y, thickness, color);
+        };
+        line(segment[0], segment[1]);
+        line(segment[n - 2], segment[n - 1]);
+        for i in 0..n / 2 - 1 {
+            line(segment[2 * i], segment[2 * i + 2]);
+            line(segment[2 * i + 1], segment[2 * i + 3]);
+        }
+    }
+
+    // Corners of the tile as a polygon, relative to the floor's corner
+    pub fn tile_outline(self, node: Node, cell_size: f32) -> Vec<Vec2> {
+        let center = self.center(node.x as f32, node.y as f32, cell_size);
+        match self {
+            Topology::Square => {
+                let h = cell_size / 2.0;
+                [(-h, -h), (h, -h), (h, h), (-h, h)]
+                    .into_iter()
+                    .map(|(dx, dy)| center + vec2(dx, dy))
+                    .collect()
+            }
+            Topology::Hex => (0..6)
+                .map(|i| {
+                    let angle = (i as f32 * 60.0 - 90.0).to_radians();
+                    center + Vec2::from_angle(angle) * cell_size / 3f32.sqrt()
+                })
+                .collect(),
+            Topology::Polar { rings } => {
+                // Along the inner edge, then back along the outer one
+                let segment = Self::polar_segment(rings, node, cell_size);
+                let inner = segment.iter().step_by(2);
+                let outer = segment.iter().skip(1).step_by(2).rev();
+                inner.chain(outer).copied().collect()
+            }
+        }
+    }
+
+    // Inner/outer point pairs along the tile's arc, relative to the floor's corner
+    fn polar_segment(rings: usize, node: Node, cell_size: f32) -> Vec<Vec2> {
+        const STEPS: usize = 4;
+        let sectors = (polar_row_tiles(node.y) / 2) as f32;
+        let (start, end) = Self::polar_arc(node.x);
+        let radius = node.y as f32 + 2.0;
+
+        (0..=STEPS)
+            .flat_map(|i| {
+                let turn = (start + (end - start) * i as f32 / STEPS as f32) / sectors;
+                [
+                    Self::polar_point(rings, radius - 0.5, turn, cell_size),
+                    Self::polar_point(rings, radius + 0.5, turn, cell_size),
+                ]
+            })
+            .collect()
+    }
+
+    // Tiles are drawn around a screen-space center so callers can place floors anywhere
+    pub fn draw_tile(self, center: Vec2, cell_size: f32, color: Color) {
+        match self {
+            Topology::Square => {
+                let h = cell_size / 2.0;
+                draw_rectangle(center.x - h, center.y - h, cell_size, cell_size, color);
+            }
+            Topology::Hex => {
+                // Pointy-top hexagon whose width equals one tile
+                draw_hexagon(
+                    center.x,
+                    center.y,
+                    cell_size / 3f32.sqrt(),
+                    0.0,
+                    true,
+                    color,
+                    color,
+                );
+            }
+            // Ring segments need the tile itself, see `draw_polar_tile`
+            Topology::Polar { .. } => {}
+        }
+    }
+
+    pub fn draw_tile_lines(self, center: Vec2, cell_size: f32, thickness: f32, color: Color) {
+        match self {
+            Topology::Square => {
+                let h = cell_size / 2.0;
+                draw_rectangle_lines(
+                    center.x - h,
+                    center.y - h,
+                    cell_size,
+                    cell_size,
+                    thickness,
+                    color,
+                );
+            }
+            Topology::Hex => {
+                draw_poly_lines(
+                    center.x,
+                    center.y,
+                    6,
+                    cell_size / 3f32.sqrt(),
+                    90.0,
+                    thickness,
+                    color,
+                );
+            }
+            Topology::Polar { .. } => {}
+        }
+    }
+}
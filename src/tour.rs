@@ -0,0 +1,201 @@
+use macroquad::prelude::*;
+
+use crate::algorithm::AStarVisualizer;
+use crate::constants::TOUR_EXACT_LIMIT;
+use crate::heuristic::ZERO;
+use crate::maze::Maze;
+use crate::node::Node;
+
+pub struct Tour {
+    pub order: Vec<Node>,
+    pub path: Vec<Node>,
+    pub cost: f32, // Summed move costs, as A* counts them
+}
+
+impl Tour {
+    pub fn draw(&self, maze: &Maze, cell_size: f32) {
+        let center = |n: &Node| maze.center(*n, cell_size);
+
+        let size = maze.view_cell_size(cell_size);
+
+        for pair in self.path.windows(2) {
+            if !maze.visible(pair[0].z) || !maze.visible(pair[1].z) {
+                continue;
+            }
+            let (a, b) = (center(&pair[0]), center(&pair[1]));
+            draw_line(a.x, a.y, b.x, b.y, 2.0, Color::new(1.0, 0.85, 0.0, 0.6));
+        }
+
+        for (i, stop) in self.order.iter().enumerate() {
+            if !maze.visible(stop.z) {
+                continue;
+            }
+            let c = center(stop);
+            draw_text(
+                (i + 1).to_string(),
+                c.x - size / 2.0,
+                c.y - size / 2.0,
+                size * 2.0,
+                YELLOW,
+            );
+        }
+    }
+}
+
+// Visit every goal starting from `start`, minimizing total route cost
+pub fn plan_tour(maze: &Maze, start: Node, goals: &[Node]) -> Option<Tour> {
+    let stops: Vec<Node> = std::iter::once(start)
+        .chain(goals.iter().copied())
+        .collect();
+    let n = stops.len();
+
+    // Pairwise shortest legs (uniform cost keeps them optimal on any topology)
+    let mut legs: Vec<Vec<Option<Vec<Node>>>> = vec![vec![None; n]; n];
+    for i in 0..n {
+        for j in i + 1..n {
+            if let Some(path) = AStarVisualizer::find_path(maze, stops[i], stops[j], &ZERO, &[]) {
+                let mut back = path.clone();
+                back.reverse();
+                legs[i][j] = Some(path);
+                legs[j][i] = Some(back);
+            }
+        }
+    }
+
+    let cost: Vec<Vec<f32>> = legs
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|leg| leg.as_ref().map_or(f32::INFINITY, |p| maze.path_cost(p)))
+                .collect()
+        })
+        .collect();
+
+    if (1..n).any(|j| legs[0][j].is_none()) {
+        return None;
+    }
+
+    let order = if n - 1 <= TOUR_EXACT_LIMIT {
+        held_karp(&cost)
+    } else {
+        two_opt(&cost, nearest_neighbour(&cost))
+    };
+
+    let mut path = vec![start];
+    let mut total = 0.0;
+    let mut prev = 0;
+    for &stop in &order {
+        let leg = legs[prev][stop].as_ref()?;
+        path.extend(leg.iter().skip(1));
+        total += cost[prev][stop];
+        prev = stop;
+    }
+
+    Some(Tour {
+        order: order.iter().map(|&i| stops[i]).collect(),
+        path,
+        cost: total,
+    })
+}
+
+// Exact open-path TSP over stops 1..n, starting from stop 0
+fn held_karp(cost: &[Vec<f32>]) -> Vec<usize> {
+    let n = cost.len();
+    let goals = n - 1;
+    if goals == 0 {
+        return Vec::new();
+    }
+
+    let full = 1usize << goals;
+    let mut dp = vec![vec![f32::INFINITY; goals]; full];
+    let mut parent = vec![vec![usize::MAX; goals]; full];
+
+    for j in 0..goals {
+        dp[1 << j][j] = cost[0][j + 1];
+    }
+
+    for mask in 1..full {
+        for last in 0..goals {
+            if mask & (1 << last) == 0 || dp[mask][last] == f32::INFINITY {
+                continue;
+            }
+            for next in 0..goals {
+                if mask & (1 << next) != 0 {
+                    continue;
+                }
+                let next_mask = mask | (1 << next);
+                let total = dp[mask][last] + cost[last + 1][next + 1];
+                if total < dp[next_mask][next] {
+                    dp[next_mask][next] = total;
+                    parent[next_mask][next] = last;
+                }
+            }
+        }
+    }
+
+    let mut last = (0..goals)
+        .min_by(|&a, &b| dp[full - 1][a].total_cmp(&dp[full - 1][b]))
+        .unwrap_or(0);
+    let mut mask = full - 1;
+    let mut order = Vec::with_capacity(goals);
+    while last != usize::MAX {
+        order.push(last + 1);
+        let prev = parent[mask][last];
+        mask &= !(1 << last);
+        last = prev;
+    }
+    order.reverse();
+    order
+}
+
+fn nearest_neighbour(cost: &[Vec<f32>]) -> Vec<usize> {
+    let n = cost.len();
+    let mut visited = vec![false; n];
+    let mut order = Vec::with_capacity(n - 1);
+    let mut current = 0;
+    visited[0] = true;
+
+    for _ in 1..n {
+        let next = (0..n)
+            .filter(|&j| !visited[j])
+            .min_by(|&a, &b| cost[current][a].total_cmp(&cost[current][b]))
+            .unwrap_or(0);
+        visited[next] = true;
+        order.push(next);
+        current = next;
+    }
+    order
+}
+
+fn route_cost(cost: &[Vec<f32>], order: &[usize]) -> f32 {
+    let mut total = 0.0;
+    let mut prev = 0;
+    for &stop in order {
+        total += cost[prev][stop];
+        prev = stop;
+    }
+    total
+}
+
+// Reverse segments of the route while that shortens it
+fn two_opt(cost: &[Vec<f32>], mut order: Vec<usize>) -> Vec<usize> {
+    let mut best = route_cost(cost, &order);
+    let mut improved = true;
+
+    while improved {
+        improved = false;
+        for i in 0..order.len() {
+            for j in i + 1..order.len() {
+                order[i..=j].reverse();
+                let total = route_cost(cost, &order);
+                if total < best {
+                    best = total;
+                    improved = true;
+                } else {
+                    order[i..=j].reverse();
+                }
+            }
+        }
+    }
+    order
+}
@@ -0,0 +1,92 @@
+use macroquad::prelude::*;
+
+use crate::constants::{MAX_ZOOM, MIN_ZOOM, PAN_THRESHOLD};
+
+// A middle-button drag in progress
+#[derive(Clone, Copy, Debug)]
+struct Pan {
+    last: Vec2,
+    moved: bool,
+}
+
+// Pan and zoom over the maze: a world point p (maze pixels at `cell_size`) lands on
+// screen at p * zoom + offset
+#[derive(Clone, Debug)]
+pub struct View {
+    pub zoom: f32,
+    pub offset: Vec2,
+    pan: Option<Pan>,
+}
+
+impl View {
+    pub fn new() -> Self {
+        Self {
+            zoom: 1.0,
+            offset: Vec2::ZERO,
+            pan: None,
+        }
+    }
+
+    pub fn camera(&self) -> Camera2D {
+        Camera2D::from_display_rect(Rect::new(
+            -self.offset.x / self.zoom,
+            -self.offset.y / self.zoom,
+            screen_width() / self.zoom,
+            screen_height() / self.zoom,
+        ))
+    }
+
+    pub fn to_world(&self, screen: Vec2) -> Vec2 {
+        (screen - self.offset) / self.zoom
+    }
+
+    // The part of the world showing through `area` (in screen pixels)
+    pub fn visible_rect(&self, area: Rect) -> Rect {
+        let min = self.to_world(area.point());
+        Rect::new(min.x, min.y, area.w / self.zoom, area.h / self.zoom)
+    }
+
+    // Zoom by `factor` keeping the world point under `anchor` in place
+    pub fn zoom_at(&mut self, anchor: Vec2, factor: f32) {
+        let zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.offset = anchor - (anchor - self.offset) * (zoom / self.zoom);
+        self.zoom = zoom;
+    }
+
+    // Scale the whole world into `area` and center it there
+    pub fn fit(&mut self, world: Vec2, area: Rect) {
+        self.zoom = (area.w / world.x)
+            .min(area.h / world.y)
+            .clamp(MIN_ZOOM, MAX_ZOOM);
+        self.offset = area.center() - world * self.zoom / 2.0;
+        self.pan = None;
+    }
+
+    pub fn begin_pan(&mut self, pointer: Vec2) {
+        self.pan = Some(Pan {
+            last: pointer,
+            moved: false,
+        });
+    }
+
+    // Small jitter doesn't count, so a click stays a click
+    pub fn drag(&mut self, pointer: Vec2) {
+        if let Some(pan) = &mut self.pan {
+            let delta = pointer - pan.last;
+            if pan.moved || delta.length() > PAN_THRESHOLD {
+                pan.moved = true;
+                pan.last = pointer;
+                self.offset += delta;
+            }
+        }
+    }
+
+    // True when the drag never moved, i.e. it was a plain click
+    pub fn end_pan(&mut self) -> bool {
+        self.pan.take().is_some_and(|pan| !pan.moved)
+    }
+
+    pub fn panning(&self) -> bool {
+        self.pan.is_some()
+    }
+}